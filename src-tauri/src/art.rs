@@ -0,0 +1,111 @@
+use crate::media::art_variant::{self, ArtVariant};
+use crate::media::{self, AlbumArt};
+use std::collections::HashMap;
+use warp::Filter;
+use warp::http::{Response, StatusCode, header};
+use warp::hyper::Body;
+use warp::hyper::body::Bytes;
+
+/// 封面内容以哈希寻址，内容不会变化，可以长期缓存
+const ART_CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+
+const ART_URL_PREFIX: &str = "/api/art/";
+
+/// `GET /api/art/{hash}`，可用 `size`、`fit`（`cover` / `contain`）、`blur` 获取缩放或模糊后的版本
+pub fn art_route() -> impl Filter<Extract = (Response<Body>,), Error = warp::Rejection> + Clone {
+    warp::path!("api" / "art" / String)
+        .and(warp::get())
+        .and(warp::query::<HashMap<String, String>>())
+        .and(warp::header::optional::<String>("if-none-match"))
+        .and_then(
+            |hash: String, query: HashMap<String, String>, if_none_match: Option<String>| async move {
+                let variant = match ArtVariant::from_query(&query) {
+                    Ok(variant) => variant,
+                    Err(e) => {
+                        return Ok(Response::builder()
+                            .status(StatusCode::BAD_REQUEST)
+                            .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
+                            .body(Body::from(e))
+                            .unwrap());
+                    }
+                };
+                // 内存未命中时会读取磁盘缓存，放到阻塞线程中
+                let art = tokio::task::spawn_blocking(move || media::get_album_art_by_hash(&hash))
+                    .await
+                    .ok()
+                    .flatten();
+                let Some(art) = art else {
+                    return Err(warp::reject::not_found());
+                };
+                // 变体由原图与参数决定，浏览器已有缓存时无需重新生成
+                let etag = if variant.is_original() {
+                    format!("\"{}\"", art.hash)
+                } else {
+                    format!("\"{}\"", variant.key(&art.hash))
+                };
+                if matches_etag(&etag, if_none_match.as_deref()) {
+                    return Ok(not_modified(&etag));
+                }
+                if variant.is_original() {
+                    return Ok(art_response(&art, &etag));
+                }
+
+                // 无法解码的图片（如 AVIF）直接返回原图，ETag 仍按请求的变体，
+                // 与上面的缓存判断保持一致
+                let rendered = tokio::task::spawn_blocking({
+                    let art = art.clone();
+                    move || art_variant::render(&art, variant)
+                })
+                .await
+                .ok()
+                .flatten();
+                Ok(art_response(rendered.as_ref().unwrap_or(&art), &etag))
+            },
+        )
+}
+
+fn matches_etag(etag: &str, if_none_match: Option<&str>) -> bool {
+    if_none_match.is_some_and(|value| {
+        value
+            .split(',')
+            .any(|tag| tag.trim() == etag || tag.trim() == "*")
+    })
+}
+
+fn not_modified(etag: &str) -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::NOT_MODIFIED)
+        .header(header::ETAG, etag)
+        .header(header::CACHE_CONTROL, ART_CACHE_CONTROL)
+        .body(Body::empty())
+        .unwrap()
+}
+
+fn art_response(art: &AlbumArt, etag: &str) -> Response<Body> {
+    Response::builder()
+        .header(header::ETAG, etag)
+        .header(header::CACHE_CONTROL, ART_CACHE_CONTROL)
+        .header(header::CONTENT_TYPE, &art.mime)
+        // 与缓存共享同一份图片数据，不复制
+        .body(Body::from(Bytes::from_owner(art.data.clone())))
+        .unwrap()
+}
+
+/// 请求是否带有 `?inline_art`，兼容读取 `data:` URI 的旧主题
+pub fn wants_inline_art(query: &HashMap<String, String>) -> bool {
+    query
+        .get("inline_art")
+        .is_some_and(|value| !matches!(value.as_str(), "0" | "false"))
+}
+
+/// 将 `/api/art/{hash}` 地址替换为内联的 `data:` URI
+pub fn inline_album_art(album_art: &mut Option<String>) {
+    let inlined = album_art
+        .as_deref()
+        .and_then(|url| url.strip_prefix(ART_URL_PREFIX))
+        .and_then(media::get_memory_album_art)
+        .map(|art| art.to_data_uri());
+    if inlined.is_some() {
+        *album_art = inlined;
+    }
+}
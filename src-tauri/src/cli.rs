@@ -8,6 +8,10 @@ use std::path::PathBuf;
 pub struct Cli {
     #[command(subcommand)]
     pub command: Option<Commands>,
+
+    /// (placeholder)
+    #[arg(long, value_name = "SCRIPT", num_args = 0..=1, default_missing_value = "")]
+    pub mock_media: Option<String>,
 }
 
 #[derive(Subcommand)]
@@ -34,6 +38,10 @@ pub struct DevArgs {
     #[arg(long, default_value = "5173")]
     pub vite_port: u16,
 
+    /// (placeholder)
+    #[arg(long, value_name = "SCRIPT", num_args = 0..=1, default_missing_value = "")]
+    pub mock: Option<String>,
+
     /// (placeholder)
     #[arg(default_value = ".")]
     pub path: PathBuf,
@@ -53,6 +61,7 @@ struct CliDevArgs {
     vite: String,
     #[serde(rename = "vite-port")]
     vite_port: String,
+    mock: String,
     path: String,
 }
 
@@ -65,6 +74,8 @@ struct CliDev {
 #[derive(Debug, Deserialize)]
 struct CliRoot {
     about: String,
+    #[serde(rename = "mock-media")]
+    mock_media: String,
     dev: CliDev,
 }
 
@@ -136,12 +147,14 @@ pub fn localized_command() -> clap::Command {
 
     Cli::command()
         .about(l10n.cli.about)
+        .mut_arg("mock_media", |a| a.help(l10n.cli.mock_media))
         .mut_subcommand("dev", |cmd| {
             cmd.about(l10n.cli.dev.about)
                 .mut_arg("port", |a| a.help(l10n.cli.dev.args.port))
                 .mut_arg("no_open", |a| a.help(l10n.cli.dev.args.no_open))
                 .mut_arg("vite", |a| a.help(l10n.cli.dev.args.vite))
                 .mut_arg("vite_port", |a| a.help(l10n.cli.dev.args.vite_port))
+                .mut_arg("mock", |a| a.help(l10n.cli.dev.args.mock))
                 .mut_arg("path", |a| a.help(l10n.cli.dev.args.path))
         })
 }
@@ -17,12 +17,76 @@ pub struct Config {
     pub current_theme: String,
     pub locale: String,
     pub process_filter: String,
+    /// 播放器优先级，每行一条规则，语法与 `process_filter` 的单条规则相同，靠前的优先
+    pub player_priority: String,
+    /// 当前播放器停止播放后继续保持展示的秒数，0 表示不保持
+    pub sticky_seconds: u64,
     /// 更新源: "github" 或 "official"（官网）
     pub update_source: String,
     /// 是否启用自动检查更新
     pub auto_check_update: bool,
     /// 是否开机自启动
     pub autostart: bool,
+    /// 播放控制接口的访问令牌，为空时禁用控制接口
+    pub control_token: String,
+    /// 封面磁盘缓存上限（MB）
+    pub art_cache_max_mb: u64,
+    /// 封面磁盘缓存保留天数，0 表示不限
+    pub art_cache_max_age_days: u64,
+    /// 媒体后端: "system"（SMTC / MPRIS）或 "mock"（脚本模拟，用于测试与演示）
+    pub media_backend: String,
+    /// 模拟后端的播放列表脚本路径（TOML/JSON），为空时使用内置演示列表
+    pub mock_script: String,
+    /// 元数据改写规则，按顺序应用
+    pub rewrite_rules: Vec<RewriteRule>,
+    /// 命名的文本模板，`/api/now.txt?template=<name>` 使用
+    pub text_templates: Vec<TextTemplate>,
+    /// 是否将正在播放的信息写入文件
+    pub file_output_enabled: bool,
+    /// 文件输出目录，为空时使用数据目录下的 `smtc2web/output`
+    pub file_output_dir: String,
+    /// 输出的文本文件，`name` 为文件名
+    pub file_output_files: Vec<TextTemplate>,
+    /// 主题文件变化时自动刷新已打开的主题页面
+    pub theme_hot_reload: bool,
+    /// 歌词文件夹（`艺术家 - 标题.lrc`），为空时使用数据目录下的 `smtc2web/lyrics`
+    pub lyrics_dir: String,
+    /// 是否记录播放历史到数据目录下的 `smtc2web/history.jsonl`
+    pub history_enabled: bool,
+}
+
+/// 命名的文本模板，语法见 `template::Template`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct TextTemplate {
+    pub name: String,
+    pub template: String,
+}
+
+/// 元数据改写规则
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct RewriteRule {
+    /// 生效的应用，语法同进程过滤器，为空时对所有应用生效
+    pub app: String,
+    /// "replace"：对 `field` 做正则替换；"split"：艺术家为空时，用带 `artist`/`title` 命名分组的正则拆分标题
+    pub kind: String,
+    /// 替换的字段: "title"、"artist" 或 "album"
+    pub field: String,
+    pub pattern: String,
+    pub replacement: String,
+}
+
+impl Default for RewriteRule {
+    fn default() -> Self {
+        Self {
+            app: "".to_string(),
+            kind: "replace".to_string(),
+            field: "title".to_string(),
+            pattern: "".to_string(),
+            replacement: "".to_string(),
+        }
+    }
 }
 
 impl Default for Config {
@@ -33,9 +97,27 @@ impl Default for Config {
             current_theme: "".to_string(),
             locale: "zh-CN".to_string(),
             process_filter: "*".to_string(),
+            player_priority: "".to_string(),
+            sticky_seconds: 10,
             update_source: "github".to_string(),
             auto_check_update: true,
             autostart: false,
+            control_token: "".to_string(),
+            art_cache_max_mb: 200,
+            art_cache_max_age_days: 30,
+            media_backend: "system".to_string(),
+            mock_script: "".to_string(),
+            rewrite_rules: Vec::new(),
+            text_templates: vec![TextTemplate {
+                name: "default".to_string(),
+                template: crate::template::DEFAULT_TEMPLATE.to_string(),
+            }],
+            file_output_enabled: false,
+            file_output_dir: "".to_string(),
+            file_output_files: crate::output::default_files(),
+            theme_hot_reload: false,
+            lyrics_dir: "".to_string(),
+            history_enabled: false,
         }
     }
 }
@@ -1,11 +1,15 @@
 use crate::cli::DevArgs;
 use crate::config::Config;
 use crate::logger;
-use crate::media::{
-    generate_song_id, get_cached_album_art, set_cached_album_art, MediaSession, PlatformSession,
+use crate::media::{self, generate_song_id, get_cached_album_art, set_cached_album_art};
+use crate::{
+    Shared, Song, apply_metadata, apply_timing, art, events, finish_song, history, log_error,
+    log_info, log_warn, lyrics, monotonic_ms,
 };
-use crate::{format_duration, log_error, log_info, log_warn, Shared, Song};
-use notify::{Config as NotifyConfig, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use notify::{
+    Config as NotifyConfig, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+};
+use std::collections::HashMap;
 use std::net::IpAddr;
 use std::path::Path;
 use std::sync::Arc;
@@ -54,8 +58,12 @@ fn parse_theme_info(theme_dir: &Path) -> Option<(String, String, String)> {
 }
 
 /* ---------- 开发模式媒体轮询 ---------- */
-fn dev_media_worker(state: Shared, process_filter: String) {
-    let session = match PlatformSession::new(&process_filter) {
+fn dev_media_worker(state: Shared, config: Config) {
+    let session = match media::create_session(
+        &config.media_backend,
+        &config.mock_script,
+        &config.process_filter,
+    ) {
         Ok(s) => s,
         Err(e) => {
             log_error!("创建媒体会话失败: {}", e);
@@ -64,9 +72,10 @@ fn dev_media_worker(state: Shared, process_filter: String) {
     };
 
     let mut last_song = Song::default();
-    let mut last_position: Option<String> = None;
     let mut last_song_id = String::new();
     let mut last_art_update: u64 = 0;
+    let mut history = history::HistoryRecorder::default();
+    history.configure(&config);
 
     loop {
         let mut current_song = Song::default();
@@ -76,67 +85,75 @@ fn dev_media_worker(state: Shared, process_filter: String) {
             .as_secs();
         current_song.last_update = timestamp;
 
-        if let Some(info) = session.poll_current() {
+        if let Some(mut info) = session.poll_current() {
+            let sampled_at = monotonic_ms();
+            media::tags::enrich(&mut info);
             current_song.is_playing = info.is_playing;
-            current_song.title = info.title;
-            current_song.artist = info.artist;
-            current_song.album = info.album;
+            apply_metadata(&mut current_song, &info);
 
-            let current_song_id =
-                generate_song_id(&current_song.title, &current_song.artist, &current_song.album);
+            let current_song_id = generate_song_id(
+                &current_song.title,
+                &current_song.artist,
+                &current_song.album,
+            );
             let cached_art = get_cached_album_art(&current_song_id);
 
-            let should_fetch_art = cached_art.is_none()
+            // 没有封面的歌曲不在每次轮询时重新获取
+            let should_fetch_art = (cached_art.is_none()
+                && !media::album_art_missing(&current_song_id))
                 || (current_song_id != last_song_id
                     && timestamp.saturating_sub(last_art_update) > 30);
 
-            if should_fetch_art {
-                current_song.album_art = session.get_album_art_base64(
-                    &current_song.artist,
-                    &current_song.title,
-                    &current_song.album,
-                );
-                if let Some(ref art) = current_song.album_art {
-                    set_cached_album_art(&current_song_id, art.clone());
+            let art = if should_fetch_art {
+                let art = session
+                    .get_album_art(
+                        &info.app_id,
+                        &current_song.artist,
+                        &current_song.title,
+                        &current_song.album,
+                    )
+                    .or_else(|| media::tags::local_album_art(&info.url));
+                match art {
+                    Some(ref art) => set_cached_album_art(&current_song_id, art.clone()),
+                    None => media::mark_album_art_missing(&current_song_id),
                 }
                 last_song_id = current_song_id;
                 last_art_update = timestamp;
+                art
             } else {
-                current_song.album_art = cached_art;
-            }
+                cached_art
+            };
+            current_song.album_art = art.as_ref().map(media::AlbumArt::url);
+            current_song.palette = art.as_ref().and_then(media::album_palette);
 
-            if info.duration_secs > 0 {
-                current_song.position = Some(format_duration(info.position_secs));
-                current_song.duration = Some(format_duration(info.duration_secs));
-                let pct = (info.position_secs as f64 * 100.0) / info.duration_secs as f64;
-                current_song.pct = Some((pct * 10.0).round() / 10.0);
-            }
+            apply_timing(&mut current_song, &info, sampled_at);
+            history.observe(&info, &current_song);
         } else {
+            history.finish();
             let empty = Song::default();
             let mut s = state.write().unwrap();
             *s = empty.clone();
+            events::publish(&last_song, &empty);
             last_song = empty;
-            last_position = None;
-            std::thread::sleep(Duration::from_millis(500));
+            session.wait_for_change(Duration::from_millis(500));
             continue;
         }
 
-        let should_update = current_song.is_playing != last_song.is_playing
-            || current_song.position != last_position
-            || current_song.title != last_song.title
-            || current_song.artist != last_song.artist
-            || current_song.album != last_song.album
-            || current_song.album_art != last_song.album_art
-            || timestamp.saturating_sub(last_song.last_update) > 10;
+        let changed = Song {
+            last_update: last_song.last_update,
+            sampled_at: last_song.sampled_at,
+            ..current_song.clone()
+        } != last_song;
+        let should_update = changed || timestamp.saturating_sub(last_song.last_update) > 10;
 
         if should_update {
             let mut s = state.write().unwrap();
             *s = current_song.clone();
+            events::publish(&last_song, &current_song);
             last_song = current_song.clone();
-            last_position = current_song.position.clone();
         }
 
-        std::thread::sleep(if current_song.is_playing {
+        session.wait_for_change(if current_song.is_playing {
             Duration::from_millis(200)
         } else {
             Duration::from_millis(1000)
@@ -152,10 +169,8 @@ async fn serve_dev_file(
     let path = if path.is_empty() { "index.html" } else { path };
     let file_path = theme_dir.join(path);
 
-    let canonical_base =
-        std::fs::canonicalize(theme_dir).map_err(|_| warp::reject::not_found())?;
-    let resolved_path =
-        std::fs::canonicalize(&file_path).map_err(|_| warp::reject::not_found())?;
+    let canonical_base = std::fs::canonicalize(theme_dir).map_err(|_| warp::reject::not_found())?;
+    let resolved_path = std::fs::canonicalize(&file_path).map_err(|_| warp::reject::not_found())?;
 
     if !resolved_path.starts_with(&canonical_base) {
         return Err(warp::reject::not_found());
@@ -219,9 +234,10 @@ async fn proxy_to_vite(
         .to_vec();
 
     let mut response = warp::http::Response::new(body);
-    response
-        .headers_mut()
-        .insert("content-type", warp::http::HeaderValue::from_str(&ct).unwrap());
+    response.headers_mut().insert(
+        "content-type",
+        warp::http::HeaderValue::from_str(&ct).unwrap(),
+    );
     Ok(response)
 }
 
@@ -255,10 +271,12 @@ fn start_file_watcher(
         let mut pending = false;
         loop {
             match rx.recv_timeout(Duration::from_millis(100)) {
-                Ok(Ok(event)) if matches!(
-                    event.kind,
-                    EventKind::Modify(_) | EventKind::Create(_) | EventKind::Remove(_)
-                ) => {
+                Ok(Ok(event))
+                    if matches!(
+                        event.kind,
+                        EventKind::Modify(_) | EventKind::Create(_) | EventKind::Remove(_)
+                    ) =>
+                {
                     pending = true;
                     while let Ok(Ok(_)) = rx.try_recv() {}
                     if pending {
@@ -266,11 +284,9 @@ fn start_file_watcher(
                         pending = false;
                     }
                 }
-                Ok(Err(_)) | Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
-                    if pending {
-                        let _ = reload_tx.send(());
-                        pending = false;
-                    }
+                Ok(Err(_)) | Err(std::sync::mpsc::RecvTimeoutError::Timeout) if pending => {
+                    let _ = reload_tx.send(());
+                    pending = false;
                 }
                 Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => break,
                 _ => {}
@@ -298,13 +314,15 @@ async fn start_vite_process(theme_dir: &Path, vite_port: u16) -> Option<tokio::p
         }
         Err(e) => {
             log_warn!("npx 启动失败: {}", e);
-            let bin = theme_dir.join("node_modules").join(".bin").join(
-                if cfg!(target_os = "windows") {
-                    "vite.cmd"
-                } else {
-                    "vite"
-                },
-            );
+            let bin =
+                theme_dir
+                    .join("node_modules")
+                    .join(".bin")
+                    .join(if cfg!(target_os = "windows") {
+                        "vite.cmd"
+                    } else {
+                        "vite"
+                    });
             if !bin.exists() {
                 log_error!("未找到 Vite 可执行文件");
                 return None;
@@ -349,11 +367,10 @@ fn sse_reload_route(
 ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
     warp::path!("__dev_reload").and(warp::get()).map(move || {
         let rx = reload_tx.subscribe();
-        let stream =
-            tokio_stream::wrappers::BroadcastStream::new(rx).map(|r| match r {
-                Ok(()) => Ok::<_, warp::Error>(warp::sse::Event::default().data("reload")),
-                Err(_) => Ok(warp::sse::Event::default().data("reload")),
-            });
+        let stream = tokio_stream::wrappers::BroadcastStream::new(rx).map(|r| match r {
+            Ok(()) => Ok::<_, warp::Error>(warp::sse::Event::default().data("reload")),
+            Err(_) => Ok(warp::sse::Event::default().data("reload")),
+        });
         warp::sse::reply(warp::sse::keep_alive().stream(stream))
     })
 }
@@ -397,19 +414,21 @@ pub async fn run(args: DevArgs) {
     }
 
     // 3. Vite 检测
-    let use_vite = args.vite
-        || VITE_CONFIG_FILES
-            .iter()
-            .any(|f| theme_dir.join(f).exists());
+    let use_vite = args.vite || VITE_CONFIG_FILES.iter().any(|f| theme_dir.join(f).exists());
 
     // 4. 媒体轮询
     let state: Shared = Arc::default();
-    let pf = Config::load()
-        .map(|c| c.process_filter)
-        .unwrap_or_else(|_| "*".to_string());
+    let mut media_config = Config::load().unwrap_or_default();
+    if let Some(script) = args.mock.clone() {
+        log_info!("使用模拟媒体后端");
+        media_config.media_backend = "mock".to_string();
+        if !script.is_empty() {
+            media_config.mock_script = script;
+        }
+    }
     std::thread::spawn({
         let s = state.clone();
-        move || dev_media_worker(s, pf)
+        move || dev_media_worker(s, media_config)
     });
 
     // 5. 热重载通道
@@ -435,7 +454,10 @@ pub async fn run(args: DevArgs) {
             println!();
             println!("  Vite 模式已启用");
             println!("  请在 vite.config 中添加代理:");
-            println!("    server: {{ proxy: {{ '/api': 'http://localhost:{}' }} }}", args.port);
+            println!(
+                "    server: {{ proxy: {{ '/api': 'http://localhost:{}' }} }}",
+                args.port
+            );
             println!();
         }
     }
@@ -447,10 +469,18 @@ pub async fn run(args: DevArgs) {
         let s = state.clone();
         move || s.clone()
     });
-    let api =
-        warp::path!("api" / "now")
-            .and(state_filter)
-            .map(|s: Shared| warp::reply::json(&*s.read().unwrap()));
+    let api = warp::path!("api" / "now")
+        .and(state_filter)
+        .and(warp::query::<HashMap<String, String>>())
+        .map(|s: Shared, query: HashMap<String, String>| {
+            let mut song = s.read().unwrap().clone();
+            finish_song(&mut song, &query);
+            warp::reply::json(&song)
+        })
+        .or(events::events_route(state.clone()))
+        .or(lyrics::lyrics_route(state.clone()))
+        .or(history::history_route())
+        .or(art::art_route());
 
     let (tx, rx) = tokio::sync::oneshot::channel::<()>();
 
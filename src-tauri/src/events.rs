@@ -0,0 +1,222 @@
+use crate::{Shared, Song, finish_song, media, monotonic_ms};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tokio_stream::StreamExt;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use warp::Filter;
+use warp::sse::Event;
+
+/// `/api/events` 推送的事件类型
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EventKind {
+    /// 换曲或元数据变化，数据为完整的 `Song`
+    Track,
+    /// 播放状态、随机、循环、音量、速率变化
+    State,
+    /// 播放进度，每秒最多一次；跳转、暂停时立即推送
+    Position,
+    /// 封面变化
+    Art,
+}
+
+impl EventKind {
+    fn name(self) -> &'static str {
+        match self {
+            EventKind::Track => "track",
+            EventKind::State => "state",
+            EventKind::Position => "position",
+            EventKind::Art => "art",
+        }
+    }
+}
+
+#[derive(Clone)]
+struct SongEvent {
+    kind: EventKind,
+    song: Arc<Song>,
+}
+
+static EVENT_BUS: Lazy<broadcast::Sender<SongEvent>> = Lazy::new(|| broadcast::channel(64).0);
+
+#[derive(Serialize)]
+struct StatePayload {
+    is_playing: bool,
+    shuffle: Option<bool>,
+    loop_status: Option<media::LoopStatus>,
+    volume: Option<f64>,
+    rate: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct PositionPayload<'a> {
+    position: &'a Option<String>,
+    duration: &'a Option<String>,
+    pct: Option<f64>,
+    position_ms: Option<u64>,
+    duration_ms: Option<u64>,
+    remaining_ms: Option<u64>,
+    is_playing: bool,
+    rate: Option<f64>,
+    sampled_at: u64,
+    server_time: u64,
+}
+
+#[derive(Serialize)]
+struct ArtPayload {
+    album_art: Option<String>,
+    palette: Option<crate::media::palette::Palette>,
+}
+
+pub(crate) fn track_changed(previous: &Song, current: &Song) -> bool {
+    previous.title != current.title
+        || previous.artist != current.artist
+        || previous.artists != current.artists
+        || previous.album != current.album
+        || previous.album_artist != current.album_artist
+        || previous.track_number != current.track_number
+        || previous.track_count != current.track_count
+        || previous.disc_number != current.disc_number
+        || previous.genres != current.genres
+        || previous.year != current.year
+        || previous.url != current.url
+        || previous.track_id != current.track_id
+        || previous.rating != current.rating
+}
+
+pub(crate) fn state_changed(previous: &Song, current: &Song) -> bool {
+    previous.is_playing != current.is_playing
+        || previous.shuffle != current.shuffle
+        || previous.loop_status != current.loop_status
+        || previous.volume != current.volume
+        || previous.rate != current.rate
+}
+
+/// 播放进度跨过整秒、时长变化，或位置与按上次采样推算的值相差超过一秒（跳转）
+fn position_changed(previous: &Song, current: &Song) -> bool {
+    if previous.duration_ms != current.duration_ms
+        || previous.position_ms.map(|ms| ms / 1000) != current.position_ms.map(|ms| ms / 1000)
+    {
+        return true;
+    }
+    let (Some(before), Some(after)) = (previous.position_ms, current.position_ms) else {
+        return false;
+    };
+    let mut expected = before as f64;
+    if previous.is_playing {
+        let elapsed = current.sampled_at.saturating_sub(previous.sampled_at) as f64;
+        expected += elapsed * previous.rate.unwrap_or(1.0);
+    }
+    (after as f64 - expected).abs() > 1000.0
+}
+
+/// 比较前后两次状态，向 `/api/events` 的订阅者推送变化，返回是否有值得推送的变化
+pub fn publish(previous: &Song, current: &Song) -> bool {
+    // 换曲时只推送完整状态，其余事件已包含在内
+    let kinds = if track_changed(previous, current) {
+        vec![EventKind::Track]
+    } else {
+        let mut kinds = Vec::new();
+        let state = state_changed(previous, current);
+        if state {
+            kinds.push(EventKind::State);
+        }
+        // 暂停、恢复或变速时同时推送位置，方便客户端重新推算进度
+        if state || position_changed(previous, current) {
+            kinds.push(EventKind::Position);
+        }
+        if previous.album_art != current.album_art {
+            kinds.push(EventKind::Art);
+        }
+        kinds
+    };
+
+    if kinds.is_empty() {
+        return false;
+    }
+    if EVENT_BUS.receiver_count() > 0 {
+        let song = Arc::new(current.clone());
+        for kind in kinds {
+            let _ = EVENT_BUS.send(SongEvent {
+                kind,
+                song: song.clone(),
+            });
+        }
+    }
+    true
+}
+
+fn build_event(kind: EventKind, song: &Song, query: &HashMap<String, String>) -> Event {
+    let event = Event::default().event(kind.name());
+    let result = match kind {
+        EventKind::Track => {
+            let mut song = song.clone();
+            finish_song(&mut song, query);
+            event.json_data(&song)
+        }
+        EventKind::State => event.json_data(StatePayload {
+            is_playing: song.is_playing,
+            shuffle: song.shuffle,
+            loop_status: song.loop_status,
+            volume: song.volume,
+            rate: song.rate,
+        }),
+        EventKind::Position => event.json_data(PositionPayload {
+            position: &song.position,
+            duration: &song.duration,
+            pct: song.pct,
+            position_ms: song.position_ms,
+            duration_ms: song.duration_ms,
+            remaining_ms: song.remaining_ms,
+            is_playing: song.is_playing,
+            rate: song.rate,
+            sampled_at: song.sampled_at,
+            server_time: monotonic_ms(),
+        }),
+        EventKind::Art => {
+            let mut album_art = song.album_art.clone();
+            if crate::art::wants_inline_art(query) {
+                crate::art::inline_album_art(&mut album_art);
+            }
+            event.json_data(ArtPayload {
+                album_art,
+                palette: song.palette.clone(),
+            })
+        }
+    };
+    result.unwrap_or_else(|e| {
+        crate::log_warn!("Failed to serialize {} event: {}", kind.name(), e);
+        Event::default().comment("serialize error")
+    })
+}
+
+/// `GET /api/events`：连接时先推送一次完整的 `track`，之后按变化推送
+pub fn events_route(
+    state: Shared,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("api" / "events")
+        .and(warp::get())
+        .and(warp::query::<HashMap<String, String>>())
+        .map(move |query: HashMap<String, String>| {
+            let state = state.clone();
+            let rx = EVENT_BUS.subscribe();
+            let initial = build_event(EventKind::Track, &state.read().unwrap(), &query);
+
+            let updates = BroadcastStream::new(rx).map(move |update| {
+                let event = match update {
+                    Ok(update) => build_event(update.kind, &update.song, &query),
+                    // 处理过慢丢失了事件，重新推送完整状态
+                    Err(BroadcastStreamRecvError::Lagged(_)) => {
+                        build_event(EventKind::Track, &state.read().unwrap(), &query)
+                    }
+                };
+                Ok::<_, Infallible>(event)
+            });
+            let stream = tokio_stream::once(Ok(initial)).chain(updates);
+            warp::sse::reply(warp::sse::keep_alive().stream(stream))
+        })
+}
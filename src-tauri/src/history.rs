@@ -0,0 +1,296 @@
+use crate::config::Config;
+use crate::{Song, log_info, log_warn, media, monotonic_ms};
+use dirs::data_dir;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use warp::Filter;
+
+const HISTORY_FILE: &str = "history.jsonl";
+
+/// 收听不足该时长的曲目不记录（快速切歌、元数据分多次补全）
+const MIN_LISTENED_MS: u64 = 1000;
+
+/// 播放到时长的该比例以上视为播放完成
+const COMPLETED_RATIO: f64 = 0.9;
+
+/// 两次轮询的间隔超过该值时不计入收听时长（例如系统休眠）
+const MAX_TICK_MS: u64 = 5000;
+
+const DEFAULT_LIMIT: usize = 50;
+const MAX_LIMIT: usize = 1000;
+
+/// 查询时只读取文件末尾的这些字节，足以容纳 `MAX_LIMIT` 条记录
+const TAIL_BYTES: u64 = 2 * 1024 * 1024;
+
+/// 一条播放记录，时间均为 Unix 毫秒
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub started_at: u64,
+    pub ended_at: u64,
+    /// 实际播放的时长，不含暂停
+    pub listened_ms: u64,
+    /// 播放到接近结尾为 `true`，中途切歌为 `false`
+    pub completed: bool,
+    pub app_id: String,
+    pub app_name: String,
+    pub title: String,
+    pub artist: String,
+    pub artists: Vec<String>,
+    pub album: String,
+    pub album_artist: String,
+    pub track_number: Option<u32>,
+    pub disc_number: Option<u32>,
+    pub genres: Vec<String>,
+    pub year: Option<i32>,
+    pub url: String,
+    pub track_id: String,
+    pub duration_ms: Option<u64>,
+    /// 记录时的封面地址，封面可能已被移出缓存
+    pub album_art: Option<String>,
+}
+
+fn history_path() -> PathBuf {
+    let mut path = data_dir().unwrap_or_else(|| PathBuf::from("."));
+    path.push("smtc2web");
+    path.push(HISTORY_FILE);
+    path
+}
+
+fn unix_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+fn append(entry: &HistoryEntry) -> std::io::Result<()> {
+    let path = history_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut line = serde_json::to_string(entry).map_err(std::io::Error::other)?;
+    line.push('\n');
+    // 整行一次写入，即使中途退出也只会留下最后一行不完整
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(line.as_bytes())
+}
+
+/// 读取文件末尾最多 `max_bytes` 字节，丢弃开头不完整的一行
+fn read_tail(path: &Path, max_bytes: u64) -> std::io::Result<String> {
+    let mut file = File::open(path)?;
+    let start = file.metadata()?.len().saturating_sub(max_bytes);
+    // 多读前一个字节，用来判断 `start` 是否正好在行首
+    file.seek(SeekFrom::Start(start.saturating_sub(1)))?;
+    let mut data = Vec::new();
+    file.read_to_end(&mut data)?;
+    if start > 0 {
+        let skip = data
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(data.len(), |i| i + 1);
+        data.drain(..skip);
+    }
+    Ok(String::from_utf8_lossy(&data).into_owned())
+}
+
+/// 按结束时间从新到旧返回记录，`since` 为 Unix 毫秒。
+/// 记录按时间追加，只读取文件末尾，更早的记录不会返回
+pub fn recent(limit: Option<usize>, since: Option<u64>) -> Vec<HistoryEntry> {
+    let Ok(content) = read_tail(&history_path(), TAIL_BYTES) else {
+        return Vec::new();
+    };
+    let mut entries: Vec<HistoryEntry> = content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .filter(|entry: &HistoryEntry| since.is_none_or(|since| entry.ended_at >= since))
+        .collect();
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.ended_at));
+    entries.truncate(limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT));
+    entries
+}
+
+/// 正在播放的曲目
+struct Playing {
+    entry: HistoryEntry,
+    last_tick: u64,
+    is_playing: bool,
+    position_ms: Option<u64>,
+}
+
+impl Playing {
+    fn new(info: &media::SessionInfo, song: &Song, now: u64) -> Self {
+        let mut playing = Self {
+            entry: HistoryEntry {
+                started_at: unix_ms(),
+                app_id: info.app_id.clone(),
+                app_name: info.app_name.clone(),
+                ..Default::default()
+            },
+            last_tick: now,
+            is_playing: song.is_playing,
+            position_ms: song.position_ms,
+        };
+        playing.update_metadata(song);
+        playing
+    }
+
+    /// 同一会话中标题与艺术家不变即视为同一首，专辑、封面等后补的字段随之更新
+    fn is_same(&self, info: &media::SessionInfo, song: &Song) -> bool {
+        self.entry.app_id == info.app_id
+            && self.entry.title == song.title
+            && self.entry.artist == song.artist
+    }
+
+    fn reached_end(&self) -> bool {
+        match (self.position_ms, self.entry.duration_ms) {
+            (Some(position), Some(duration)) if duration > 0 => {
+                position as f64 >= duration as f64 * COMPLETED_RATIO
+            }
+            _ => false,
+        }
+    }
+
+    /// 单曲循环时进度从结尾回到开头，算作新的一次播放
+    fn restarted(&self, song: &Song) -> bool {
+        self.reached_end()
+            && song
+                .position_ms
+                .zip(self.entry.duration_ms)
+                .is_some_and(|(position, duration)| {
+                    (position as f64) < duration as f64 * (1.0 - COMPLETED_RATIO)
+                })
+    }
+
+    fn update_metadata(&mut self, song: &Song) {
+        let entry = &mut self.entry;
+        entry.title = song.title.clone();
+        entry.artist = song.artist.clone();
+        entry.artists = song.artists.clone();
+        entry.album = song.album.clone();
+        entry.album_artist = song.album_artist.clone();
+        entry.track_number = song.track_number;
+        entry.disc_number = song.disc_number;
+        entry.genres = song.genres.clone();
+        entry.year = song.year;
+        entry.url = song.url.clone();
+        entry.track_id = song.track_id.clone();
+        entry.duration_ms = song.duration_ms.or(entry.duration_ms);
+        entry.album_art = song.album_art.clone().or(entry.album_art.take());
+    }
+
+    /// 上次轮询时在播放，则计入这段时间
+    fn tick(&mut self, now: u64) {
+        let elapsed = now.saturating_sub(self.last_tick);
+        if self.is_playing && elapsed <= MAX_TICK_MS {
+            self.entry.listened_ms += elapsed;
+        }
+        self.last_tick = now;
+    }
+
+    fn advance(&mut self, song: &Song, now: u64) {
+        self.tick(now);
+        self.is_playing = song.is_playing;
+        self.position_ms = song.position_ms.or(self.position_ms);
+        self.update_metadata(song);
+    }
+}
+
+/// 记录播放历史，追加写入数据目录下的 `smtc2web/history.jsonl`。
+///
+/// 曲目切换、会话消失时结束上一首的记录。
+#[derive(Default)]
+pub struct HistoryRecorder {
+    enabled: bool,
+    playing: Option<Playing>,
+}
+
+impl HistoryRecorder {
+    pub fn configure(&mut self, config: &Config) {
+        if self.enabled != config.history_enabled {
+            self.enabled = config.history_enabled;
+            self.playing = None;
+            if self.enabled {
+                log_info!("Play history enabled: {}", history_path().display());
+            }
+        }
+    }
+
+    /// 每次轮询调用，传入当前会话与处理后的歌曲信息
+    pub fn observe(&mut self, info: &media::SessionInfo, song: &Song) {
+        if !self.enabled {
+            return;
+        }
+        let now = monotonic_ms();
+        if let Some(playing) = &mut self.playing {
+            if playing.is_same(info, song) && !playing.restarted(song) {
+                playing.advance(song, now);
+                return;
+            }
+            playing.tick(now);
+            self.finish();
+        }
+        if !song.title.is_empty() {
+            self.playing = Some(Playing::new(info, song, now));
+        }
+    }
+
+    /// 结束当前曲目的记录，没有会话时调用
+    pub fn finish(&mut self) {
+        let Some(playing) = self.playing.take() else {
+            return;
+        };
+        if playing.entry.listened_ms < MIN_LISTENED_MS {
+            return;
+        }
+        let completed = playing.reached_end();
+        let entry = HistoryEntry {
+            ended_at: unix_ms(),
+            completed,
+            ..playing.entry
+        };
+        if let Err(e) = append(&entry) {
+            log_warn!("Failed to write play history: {}", e);
+        }
+    }
+}
+
+/// `GET /api/history?limit=&since=`
+pub fn history_route() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+{
+    warp::path!("api" / "history")
+        .and(warp::get())
+        .and(warp::query::<HashMap<String, String>>())
+        .and_then(|query: HashMap<String, String>| async move {
+            let limit = query.get("limit").and_then(|limit| limit.parse().ok());
+            let since = query.get("since").and_then(|since| since.parse().ok());
+            // 读取历史文件放到阻塞线程中
+            let entries = tokio::task::spawn_blocking(move || recent(limit, since))
+                .await
+                .unwrap_or_default();
+            Ok::<_, warp::Rejection>(warp::reply::json(&entries))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_tail_keeps_whole_lines() {
+        let path = std::env::temp_dir().join(format!("smtc2web-history-{}", std::process::id()));
+        fs::write(&path, "first\nsecond\nthird\n").unwrap();
+        assert_eq!(read_tail(&path, 100).unwrap(), "first\nsecond\nthird\n");
+        // 从 "second" 行首开始时保留该行
+        assert_eq!(read_tail(&path, 13).unwrap(), "second\nthird\n");
+        // 从行中开始时丢弃不完整的部分
+        assert_eq!(read_tail(&path, 10).unwrap(), "third\n");
+        assert_eq!(read_tail(&path, 3).unwrap(), "");
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -10,6 +10,8 @@ pub struct TrayTranslations {
     pub show_window: String,
     pub open_web: String,
     pub check_update: String,
+    pub lock_player: String,
+    pub reload_overlays: String,
     pub quit: String,
 }
 
@@ -110,5 +112,3 @@ pub fn get_current_locale_data() -> Option<Locale> {
 pub fn is_locale_supported(locale: &str) -> bool {
     matches!(locale, "zh-CN" | "en")
 }
-
-
@@ -1,6 +1,7 @@
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::net::IpAddr;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex, RwLock};
@@ -9,14 +10,21 @@ use tauri::Manager;
 use tokio::sync::oneshot;
 use warp::Filter;
 
+mod art;
 mod config;
+mod events;
+mod history;
 mod i18n;
 mod logger;
+mod lyrics;
 mod media;
+mod output;
+mod template;
 mod theme;
 mod theme_manager;
 mod tray;
 mod updater;
+mod ws;
 
 #[cfg(feature = "dev")]
 pub mod cli;
@@ -26,29 +34,96 @@ pub mod dev;
 #[derive(Default, Clone, Serialize, PartialEq)]
 pub struct Song {
     pub title: String,
+    /// 以 ", " 连接的艺术家，新主题请使用 `artists`
     pub artist: String,
+    pub artists: Vec<String>,
     pub album: String,
+    pub album_artist: String,
+    pub track_number: Option<u32>,
+    pub track_count: Option<u32>,
+    pub disc_number: Option<u32>,
+    pub genres: Vec<String>,
+    pub year: Option<i32>,
+    /// 曲目来源地址，可用于链接回播放器或网页
+    pub url: String,
+    pub track_id: String,
+    /// 用户评分，0.0 ~ 1.0
+    pub rating: Option<f64>,
+    pub shuffle: Option<bool>,
+    /// `none` / `track` / `playlist`
+    pub loop_status: Option<media::LoopStatus>,
+    /// 音量，0.0 ~ 1.0
+    pub volume: Option<f64>,
+    /// 播放速率，1.0 为正常速度
+    pub rate: Option<f64>,
+    /// 封面地址 `/api/art/{hash}`，请求带 `?inline_art` 时为 `data:` URI
     pub album_art: Option<String>,
+    /// 从封面提取的配色，没有封面时为空
+    pub palette: Option<media::palette::Palette>,
+    /// 格式化的位置与时长，`mm:ss`，超过一小时为 `h:mm:ss`
     pub position: Option<String>,
     pub duration: Option<String>,
     pub pct: Option<f64>,
+    pub position_ms: Option<u64>,
+    pub duration_ms: Option<u64>,
+    pub remaining_ms: Option<u64>,
+    /// 采样时的单调时间（毫秒），与 `server_time` 相减即为采样后经过的时间
+    pub sampled_at: u64,
+    /// 生成响应时的单调时间（毫秒）
+    pub server_time: u64,
     pub is_playing: bool,
     pub last_update: u64,
 }
 
 pub fn format_duration(seconds: u64) -> String {
-    let minutes = seconds / 60;
+    let hours = seconds / 3600;
+    let minutes = seconds % 3600 / 60;
     let secs = seconds % 60;
-    format!("{:02}:{:02}", minutes, secs)
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, secs)
+    } else {
+        format!("{:02}:{:02}", minutes, secs)
+    }
+}
+
+static MONOTONIC_START: once_cell::sync::Lazy<std::time::Instant> =
+    once_cell::sync::Lazy::new(std::time::Instant::now);
+
+/// 自程序启动以来的单调时间（毫秒），不受系统时间调整影响
+pub fn monotonic_ms() -> u64 {
+    MONOTONIC_START.elapsed().as_millis() as u64
+}
+
+/// 填充响应时才确定的字段
+pub(crate) fn finish_song(song: &mut Song, query: &HashMap<String, String>) {
+    song.server_time = monotonic_ms();
+    if art::wants_inline_art(query) {
+        art::inline_album_art(&mut song.album_art);
+    }
 }
 
 pub type Shared = Arc<RwLock<Song>>;
 
+/// 单个媒体会话的播放信息，`/api/sessions` 的列表项
+#[derive(Default, Clone, Serialize, PartialEq)]
+pub struct SessionEntry {
+    pub app_id: String,
+    pub app_name: String,
+    /// 是否为 `/api/now` 当前展示的会话
+    pub is_current: bool,
+    #[serde(flatten)]
+    pub song: Song,
+}
+
+pub type SharedSessions = Arc<RwLock<Vec<SessionEntry>>>;
+
 struct AppState {
     config: Arc<Mutex<config::Config>>,
-    server_tx: Option<oneshot::Sender<()>>,
     server_port: u16,
     shared_state: Option<Shared>,
+    shared_sessions: Option<SharedSessions>,
+    media_session: Option<Arc<dyn media::MediaSession>>,
+    theme_manager: Option<theme::ThemeManager>,
 }
 
 static CURRENT_APP_ID: once_cell::sync::Lazy<Mutex<String>> =
@@ -60,9 +135,11 @@ static CURRENT_APP_DISPLAY_NAME: once_cell::sync::Lazy<Mutex<String>> =
 static APP_STATE: once_cell::sync::Lazy<Mutex<AppState>> = once_cell::sync::Lazy::new(|| {
     Mutex::new(AppState {
         config: Arc::new(Mutex::new(config::Config::default())),
-        server_tx: None,
         server_port: 3030,
         shared_state: None,
+        shared_sessions: None,
+        media_session: None,
+        theme_manager: None,
     })
 });
 
@@ -74,6 +151,12 @@ fn with_state(
     warp::any().map(move || s.clone())
 }
 
+fn with_sessions(
+    s: SharedSessions,
+) -> impl Filter<Extract = (SharedSessions,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || s.clone())
+}
+
 // 单进程检测 - 跨平台实现
 use open::that;
 #[cfg(target_os = "windows")]
@@ -151,8 +234,9 @@ impl SingleInstance {
     fn new(name: &str) -> Result<Self, String> {
         use named_lock::NamedLock;
 
-        let lock: &'static NamedLock =
-            Box::leak(Box::new(NamedLock::create(name).map_err(|e| format!("创建互斥锁失败: {}", e))?));
+        let lock: &'static NamedLock = Box::leak(Box::new(
+            NamedLock::create(name).map_err(|e| format!("创建互斥锁失败: {}", e))?,
+        ));
 
         match lock.try_lock() {
             Ok(guard) => Ok(SingleInstance { _guard: guard }),
@@ -175,17 +259,87 @@ fn check_single_instance() -> Result<SingleInstance, String> {
 }
 
 // -------------------- 后台轮询 --------------------
-fn media_worker(state: Shared) {
-    use media::{MediaSession, PlatformSession};
-    use std::time::{SystemTime, UNIX_EPOCH};
 
-    let process_filter = {
-        let app_state = APP_STATE.lock().unwrap();
-        let config = app_state.config.lock().unwrap();
-        config.process_filter.clone()
-    };
+/// 填充播放进度字段（不含封面）
+pub(crate) fn apply_metadata(song: &mut Song, info: &media::SessionInfo) {
+    song.title = info.title.clone();
+    song.artist = info.artist.clone();
+    song.artists = info.artists.clone();
+    song.album = info.album.clone();
+    song.album_artist = info.album_artist.clone();
+    song.track_number = info.track_number;
+    song.track_count = info.track_count;
+    song.disc_number = info.disc_number;
+    song.genres = info.genres.clone();
+    song.year = info.year;
+    song.url = info.url.clone();
+    song.track_id = info.track_id.clone();
+    song.rating = info.rating;
+    song.shuffle = info.shuffle;
+    song.loop_status = info.loop_status;
+    song.volume = info.volume;
+    song.rate = info.rate;
+}
 
-    let session = match PlatformSession::new(&process_filter) {
+pub(crate) fn apply_timing(song: &mut Song, info: &media::SessionInfo, sampled_at: u64) {
+    song.sampled_at = sampled_at;
+    if info.duration_ms > 0 {
+        let position_ms = info.position_ms.min(info.duration_ms);
+        song.position = Some(format_duration(position_ms / 1000));
+        song.duration = Some(format_duration(info.duration_ms / 1000));
+        let percentage = (position_ms as f64 * 100.0) / info.duration_ms as f64;
+        song.pct = Some((percentage * 10.0).round() / 10.0);
+        song.position_ms = Some(position_ms);
+        song.duration_ms = Some(info.duration_ms);
+        song.remaining_ms = Some(info.duration_ms - position_ms);
+    } else {
+        // 直播等没有时长的内容只提供位置
+        song.position_ms = Some(info.position_ms);
+    }
+}
+
+/// 将所有会话转换为 `/api/sessions` 列表，封面仅取自缓存
+fn build_session_entries(
+    infos: Vec<media::SessionInfo>,
+    current_app_id: Option<&str>,
+    timestamp: u64,
+    sampled_at: u64,
+) -> Vec<SessionEntry> {
+    infos
+        .into_iter()
+        .map(|info| {
+            let mut song = Song {
+                is_playing: info.is_playing,
+                last_update: timestamp,
+                ..Default::default()
+            };
+            apply_metadata(&mut song, &info);
+            let art = media::get_cached_album_art(&media::generate_song_id(
+                &song.title,
+                &song.artist,
+                &song.album,
+            ));
+            song.album_art = art.as_ref().map(media::AlbumArt::url);
+            song.palette = art.as_ref().and_then(media::album_palette);
+            apply_timing(&mut song, &info, sampled_at);
+            SessionEntry {
+                is_current: current_app_id == Some(info.app_id.as_str()),
+                app_id: info.app_id,
+                app_name: info.app_name,
+                song,
+            }
+        })
+        .collect()
+}
+
+fn media_worker(state: Shared, sessions: SharedSessions, config: config::Config) {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    let session = match media::create_session(
+        &config.media_backend,
+        &config.mock_script,
+        &config.process_filter,
+    ) {
         Ok(s) => s,
         Err(e) => {
             log_error!("Failed to create media session: {}", e);
@@ -193,10 +347,17 @@ fn media_worker(state: Shared) {
         }
     };
 
+    APP_STATE.lock().unwrap().media_session = Some(session.clone());
+
     let mut last_song = Song::default();
-    let mut last_position = None::<String>;
     let mut last_song_id = String::new();
     let mut last_art_update = 0u64;
+    let mut last_sessions_push = std::time::Instant::now();
+    let mut selector = media::selector::SessionSelector::new();
+    let mut rewrite_rules = config.rewrite_rules.clone();
+    let mut rewriter = media::rewrite::Rewriter::compile_lossy(&rewrite_rules);
+    let mut file_output = output::FileOutput::default();
+    let mut history = history::HistoryRecorder::default();
 
     loop {
         let mut current_song = Song::default();
@@ -206,7 +367,57 @@ fn media_worker(state: Shared) {
             .as_secs();
         current_song.last_update = timestamp;
 
-        if let Some(info) = session.poll_current() {
+        let (priority, sticky) = {
+            let app_state = APP_STATE.lock().unwrap();
+            let config = app_state.config.lock().unwrap();
+            if config.rewrite_rules != rewrite_rules {
+                rewrite_rules = config.rewrite_rules.clone();
+                rewriter = media::rewrite::Rewriter::compile_lossy(&rewrite_rules);
+            }
+            file_output.configure(&config);
+            history.configure(&config);
+            (
+                config.player_priority.clone(),
+                Duration::from_secs(config.sticky_seconds),
+            )
+        };
+
+        let mut infos = session.list_sessions();
+        let sampled_at = monotonic_ms();
+        for info in &mut infos {
+            // 先用本地文件标签补全，改写规则作用于补全后的元数据
+            media::tags::enrich(info);
+            rewriter.apply(info);
+        }
+        let current = selector.select(&infos, &priority, sticky).cloned();
+
+        let entries = build_session_entries(
+            infos,
+            current.as_ref().map(|info| info.app_id.as_str()),
+            timestamp,
+            sampled_at,
+        );
+        {
+            let mut s = sessions.write().unwrap();
+            if *s != entries {
+                // 进度变化每秒最多推送一次，会话或曲目变化立即推送
+                let structural = s.len() != entries.len()
+                    || s.iter().zip(&entries).any(|(a, b)| {
+                        a.app_id != b.app_id
+                            || a.is_current != b.is_current
+                            || a.song.is_playing != b.song.is_playing
+                            || a.song.title != b.song.title
+                            || a.song.album_art != b.song.album_art
+                    });
+                if structural || last_sessions_push.elapsed() >= Duration::from_secs(1) {
+                    ws::publish_sessions(&entries);
+                    last_sessions_push = std::time::Instant::now();
+                }
+                *s = entries;
+            }
+        }
+
+        if let Some(info) = current {
             {
                 let mut current_app = CURRENT_APP_ID.lock().unwrap();
                 *current_app = info.app_id.clone();
@@ -217,9 +428,7 @@ fn media_worker(state: Shared) {
             }
 
             current_song.is_playing = info.is_playing;
-            current_song.title = info.title;
-            current_song.artist = info.artist;
-            current_song.album = info.album;
+            apply_metadata(&mut current_song, &info);
 
             let current_song_id = media::generate_song_id(
                 &current_song.title,
@@ -229,67 +438,229 @@ fn media_worker(state: Shared) {
 
             let cached_art = media::get_cached_album_art(&current_song_id);
 
-            let should_fetch_art = cached_art.is_none()
+            // 没有封面的歌曲不在每次轮询时重新获取
+            let should_fetch_art = (cached_art.is_none()
+                && !media::album_art_missing(&current_song_id))
                 || (current_song_id != last_song_id
                     && timestamp.saturating_sub(last_art_update) > 30);
 
-            if should_fetch_art {
-                current_song.album_art = session.get_album_art_base64(
-                    &current_song.artist,
-                    &current_song.title,
-                    &current_song.album,
-                );
+            let art = if should_fetch_art {
+                let art = session
+                    .get_album_art(
+                        &info.app_id,
+                        &current_song.artist,
+                        &current_song.title,
+                        &current_song.album,
+                    )
+                    .or_else(|| {
+                        // 播放器没有提供封面时，使用本地文件的内嵌封面或文件夹图片
+                        let art = media::tags::local_album_art(&info.url)?;
+                        media::set_cached_album_art(&current_song_id, art.clone());
+                        Some(art)
+                    });
+                if art.is_none() {
+                    media::mark_album_art_missing(&current_song_id);
+                }
                 last_song_id = current_song_id;
                 last_art_update = timestamp;
+                art
             } else {
-                current_song.album_art = cached_art;
-            }
+                cached_art
+            };
+            current_song.album_art = art.as_ref().map(media::AlbumArt::url);
+            current_song.palette = art.as_ref().and_then(media::album_palette);
 
-            if info.duration_secs > 0 {
-                current_song.position = Some(format_duration(info.position_secs));
-                current_song.duration = Some(format_duration(info.duration_secs));
-                let percentage = (info.position_secs as f64 * 100.0) / info.duration_secs as f64;
-                current_song.pct = Some((percentage * 10.0).round() / 10.0);
-            }
+            apply_timing(&mut current_song, &info, sampled_at);
+            history.observe(&info, &current_song);
         } else {
+            history.finish();
             let empty_song = Song::default();
-            let mut s = state.write().unwrap();
-            *s = empty_song.clone();
+            // 写文件前释放锁，避免阻塞接口读取
+            *state.write().unwrap() = empty_song.clone();
+            let notify = events::publish(&last_song, &empty_song);
+            if notify {
+                ws::publish_now(&empty_song);
+            }
+            file_output.write(&empty_song);
             last_song = empty_song.clone();
-            last_position = None;
 
-            std::thread::sleep(Duration::from_millis(500));
+            session.wait_for_change(Duration::from_millis(500));
             continue;
         }
 
-        let should_update = current_song.is_playing != last_song.is_playing
-            || current_song.position != last_position
-            || current_song.title != last_song.title
-            || current_song.artist != last_song.artist
-            || current_song.album != last_song.album
-            || current_song.album_art != last_song.album_art
-            || timestamp.saturating_sub(last_song.last_update) > 10;
+        // 除时间戳外任意字段变化都立即推送（部分播放器会分多次补全元数据）
+        let changed = Song {
+            last_update: last_song.last_update,
+            sampled_at: last_song.sampled_at,
+            ..current_song.clone()
+        } != last_song;
+        let should_update = changed || timestamp.saturating_sub(last_song.last_update) > 10;
 
         if should_update {
-            let mut s = state.write().unwrap();
-            *s = current_song.clone();
+            // 写文件前释放锁，避免阻塞接口读取
+            *state.write().unwrap() = current_song.clone();
+            let notify = events::publish(&last_song, &current_song);
+            if notify {
+                ws::publish_now(&current_song);
+            }
+            file_output.write(&current_song);
             last_song = current_song.clone();
-            last_position = current_song.position.clone();
         }
 
         let sleep_duration = match current_song.is_playing {
             true => Duration::from_millis(200),
             false => Duration::from_millis(1000),
         };
-        std::thread::sleep(sleep_duration);
+        session.wait_for_change(sleep_duration);
+    }
+}
+
+/// 向指定会话发送播放控制命令，未指定时使用当前会话
+fn send_media_command(
+    app_id: Option<String>,
+    command: &media::PlaybackCommand,
+) -> Result<(), String> {
+    let session = {
+        let app_state = APP_STATE.lock().map_err(|e| e.to_string())?;
+        app_state
+            .media_session
+            .clone()
+            .ok_or("Media session not initialized")?
+    };
+
+    let app_id = match app_id.filter(|id| !id.is_empty()) {
+        Some(id) => id,
+        None => CURRENT_APP_ID.lock().map_err(|e| e.to_string())?.clone(),
+    };
+    if app_id.is_empty() {
+        return Err("No active media session".to_string());
     }
+
+    log_info!("Media control: {:?} -> {}", command, app_id);
+    session.control(&app_id, command)
+}
+
+/// `POST /api/control/{action}` 的可选请求体
+#[derive(Default, Deserialize)]
+struct ControlRequest {
+    app_id: Option<String>,
+    position_ms: Option<u64>,
+    volume: Option<f64>,
+}
+
+/// 将控制动作名转换为命令，未知动作返回 `Ok(None)`
+fn parse_control_action(
+    action: &str,
+    request: &ControlRequest,
+) -> Result<Option<media::PlaybackCommand>, String> {
+    let command = match action {
+        "play" => media::PlaybackCommand::Play,
+        "pause" => media::PlaybackCommand::Pause,
+        "play_pause" | "toggle" => media::PlaybackCommand::PlayPause,
+        "next" => media::PlaybackCommand::Next,
+        "previous" => media::PlaybackCommand::Previous,
+        "seek" => media::PlaybackCommand::Seek {
+            position_ms: request.position_ms.ok_or("Missing position_ms")?,
+        },
+        "volume" => media::PlaybackCommand::SetVolume {
+            volume: request.volume.ok_or("Missing volume")?,
+        },
+        _ => return Ok(None),
+    };
+    Ok(Some(command))
+}
+
+/// 在阻塞线程池中执行控制命令（D-Bus / WinRT 调用是阻塞的）
+async fn run_media_command(
+    app_id: Option<String>,
+    command: media::PlaybackCommand,
+) -> Result<(), String> {
+    tokio::task::spawn_blocking(move || send_media_command(app_id, &command))
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|r| r)
+}
+
+fn control_reply(
+    status: warp::http::StatusCode,
+    error: Option<String>,
+) -> warp::reply::WithStatus<warp::reply::Json> {
+    let body = match error {
+        Some(e) => serde_json::json!({ "ok": false, "error": e }),
+        None => serde_json::json!({ "ok": true }),
+    };
+    warp::reply::with_status(warp::reply::json(&body), status)
+}
+
+/// 取出 `Authorization: Bearer <token>` 中的令牌
+fn bearer_token(authorization: Option<&str>) -> Option<&str> {
+    authorization.and_then(|h| h.strip_prefix("Bearer "))
+}
+
+/// 校验控制接口令牌。比较前先取哈希，耗时与令牌内容和长度无关
+fn check_control_token(provided: Option<&str>) -> Result<(), String> {
+    use sha2::{Digest, Sha256};
+
+    let expected = {
+        let app_state = APP_STATE.lock().map_err(|e| e.to_string())?;
+        let config = app_state.config.lock().map_err(|e| e.to_string())?;
+        config.control_token.clone()
+    };
+    if expected.is_empty() {
+        return Err("Control API is disabled, set a control token in settings".to_string());
+    }
+
+    let provided = Sha256::digest(provided.unwrap_or_default().trim());
+    let expected = Sha256::digest(&expected);
+    let diff = provided
+        .iter()
+        .zip(expected.iter())
+        .fold(0u8, |diff, (a, b)| diff | (a ^ b));
+    if diff == 0 {
+        Ok(())
+    } else {
+        Err("Invalid control token".to_string())
+    }
+}
+
+async fn handle_control_request(
+    action: String,
+    authorization: Option<String>,
+    body: warp::hyper::body::Bytes,
+) -> Result<warp::reply::WithStatus<warp::reply::Json>, warp::Rejection> {
+    use warp::http::StatusCode;
+
+    if let Err(e) = check_control_token(bearer_token(authorization.as_deref())) {
+        return Ok(control_reply(StatusCode::UNAUTHORIZED, Some(e)));
+    }
+
+    let request: ControlRequest = if body.is_empty() {
+        ControlRequest::default()
+    } else {
+        match serde_json::from_slice(&body) {
+            Ok(r) => r,
+            Err(e) => return Ok(control_reply(StatusCode::BAD_REQUEST, Some(e.to_string()))),
+        }
+    };
+
+    let command = match parse_control_action(&action, &request) {
+        Ok(Some(command)) => command,
+        Ok(None) => return Err(warp::reject::not_found()),
+        Err(e) => return Ok(control_reply(StatusCode::BAD_REQUEST, Some(e))),
+    };
+
+    Ok(match run_media_command(request.app_id, command).await {
+        Ok(()) => control_reply(StatusCode::OK, None),
+        Err(e) => control_reply(StatusCode::BAD_GATEWAY, Some(e)),
+    })
 }
 
 // 启动 Web 服务器
 async fn start_server(
     state: Shared,
+    sessions: SharedSessions,
     port: u16,
-    current_theme: String,
+    theme_manager: theme::ThemeManager,
 ) -> (oneshot::Sender<()>, tokio::task::JoinHandle<()>) {
     let address = {
         let app_state = APP_STATE.lock().unwrap();
@@ -300,23 +671,72 @@ async fn start_server(
             .expect("Invalid IP address in config")
     };
 
-    let theme_path = if current_theme.is_empty() || current_theme == "default" {
-        PathBuf::new()
-    } else {
-        theme_manager::ThemeManager::get_theme_server_path(&current_theme)
-    };
-
-    let theme_manager = theme::ThemeManager::new(&theme_path.to_string_lossy());
+    let events_api = events::events_route(state.clone());
+    let ws_api = ws::ws_route();
+    let now_text_api = template::now_text_route(state.clone());
+    let lyrics_api = lyrics::lyrics_route(state.clone());
+    let history_api = history::history_route();
 
     let api = warp::path!("api" / "now")
         .and(with_state(state))
-        .map(|s: Shared| warp::reply::json(&*s.read().unwrap()));
+        .and(warp::query::<HashMap<String, String>>())
+        .map(|s: Shared, query: HashMap<String, String>| {
+            let mut song = s.read().unwrap().clone();
+            finish_song(&mut song, &query);
+            warp::reply::json(&song)
+        });
+
+    let sessions_api = warp::path!("api" / "sessions")
+        .and(with_sessions(sessions.clone()))
+        .and(warp::query::<HashMap<String, String>>())
+        .map(|s: SharedSessions, query: HashMap<String, String>| {
+            let mut entries = s.read().unwrap().clone();
+            for entry in &mut entries {
+                finish_song(&mut entry.song, &query);
+            }
+            warp::reply::json(&entries)
+        });
+
+    let session_api = warp::path!("api" / "sessions" / String)
+        .and(with_sessions(sessions))
+        .and(warp::query::<HashMap<String, String>>())
+        .and_then(
+            |id: String, s: SharedSessions, query: HashMap<String, String>| async move {
+                // app_id 可能包含 `!` 等字符，客户端通常会进行百分号编码
+                let id = percent_encoding::percent_decode_str(&id)
+                    .decode_utf8_lossy()
+                    .to_string();
+                let sessions = s.read().unwrap();
+                match sessions
+                    .iter()
+                    .find(|entry| entry.app_id.eq_ignore_ascii_case(&id))
+                {
+                    Some(entry) => {
+                        let mut entry = entry.clone();
+                        finish_song(&mut entry.song, &query);
+                        Ok(warp::reply::json(&entry))
+                    }
+                    None => Err(warp::reject::not_found()),
+                }
+            },
+        );
+
+    let art_api = art::art_route();
+
+    let control_api = warp::path!("api" / "control" / String)
+        .and(warp::post())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(warp::body::content_length_limit(16 * 1024))
+        .and(warp::body::bytes())
+        .and_then(handle_control_request);
 
     let theme_files = warp::path("theme")
         .and(warp::path::tail())
         .and(theme::ThemeManager::with_manager(theme_manager.clone()))
         .and_then(|tail, manager: theme::ThemeManager| manager.serve_theme_file(tail));
 
+    let pinned_themes = theme::pinned_theme_route();
+
     let static_files = warp::path::tail()
         .and(theme::ThemeManager::with_manager(theme_manager))
         .and_then(|tail, manager: theme::ThemeManager| manager.serve_theme_file(tail));
@@ -324,10 +744,22 @@ async fn start_server(
     let (tx, rx) = oneshot::channel::<()>();
 
     let server_handle = tokio::spawn(async move {
-        let (_, server) = warp::serve(api.or(theme_files).or(static_files))
-            .bind_with_graceful_shutdown((address, port), async {
-                let _ = rx.await;
-            });
+        let routes = api
+            .or(now_text_api)
+            .or(lyrics_api)
+            .or(history_api)
+            .or(events_api)
+            .or(ws_api)
+            .or(sessions_api)
+            .or(session_api)
+            .or(control_api)
+            .or(art_api)
+            .or(theme_files)
+            .or(pinned_themes)
+            .or(static_files);
+        let (_, server) = warp::serve(routes).bind_with_graceful_shutdown((address, port), async {
+            let _ = rx.await;
+        });
         server.await;
     });
 
@@ -336,6 +768,15 @@ async fn start_server(
     (tx, server_handle)
 }
 
+/// 主题文件夹名对应的目录，默认主题为空路径
+fn theme_server_path(theme_name: &str) -> PathBuf {
+    if theme_name.is_empty() || theme_name == "default" {
+        PathBuf::new()
+    } else {
+        theme_manager::ThemeManager::get_theme_server_path(theme_name)
+    }
+}
+
 // -------------------- Tauri 命令 --------------------
 
 #[tauri::command]
@@ -352,40 +793,40 @@ async fn get_current_theme() -> Result<String, String> {
 
 #[tauri::command]
 async fn set_theme(theme_name: String, _app_handle: tauri::AppHandle) -> Result<(), String> {
-    let (port, state) = {
-        let mut app_state = APP_STATE.lock().map_err(|e| e.to_string())?;
-        if let Some(tx) = app_state.server_tx.take() {
-            let _ = tx.send(());
-        }
-
-        {
-            let mut config = app_state.config.lock().map_err(|e| e.to_string())?;
-            config.current_theme = theme_name.clone();
-            config.save().map_err(|e| e.to_string())?;
-        }
-
-        let port = app_state
-            .config
-            .lock()
-            .map_err(|e| e.to_string())?
-            .server_port;
-        let state = app_state
-            .shared_state
+    let (manager, config) = {
+        let app_state = APP_STATE.lock().map_err(|e| e.to_string())?;
+        let manager = app_state
+            .theme_manager
             .clone()
-            .ok_or("Shared state not initialized")?;
-        (port, state)
+            .ok_or("Theme manager not initialized")?;
+        (manager, app_state.config.clone())
     };
 
-    let (tx, _) = start_server(state, port, theme_name).await;
+    // 写入磁盘前释放锁，避免阻塞媒体线程与接口读取配置
+    let snapshot = {
+        let mut config = config.lock().map_err(|e| e.to_string())?;
+        config.current_theme = theme_name.clone();
+        config.clone()
+    };
+    snapshot.save().map_err(|e| e.to_string())?;
+    ws::publish_config(&snapshot);
 
-    {
-        let mut app_state = APP_STATE.lock().map_err(|e| e.to_string())?;
-        app_state.server_tx = Some(tx);
-    }
+    // 服务器按请求读取当前主题，已连接的页面收到推送后自行刷新
+    manager.set_theme_path(theme_server_path(&theme_name));
+    log_info!("Theme switched to: {}", theme_name);
+    ws::publish_theme(&theme_name);
 
     Ok(())
 }
 
+/// 刷新所有已打开的主题页面
+#[tauri::command]
+async fn reload_overlays() -> Result<(), String> {
+    log_info!("Reloading all overlays");
+    ws::publish_theme_reload(None);
+    Ok(())
+}
+
 #[tauri::command]
 async fn upload_theme(file_path: String) -> Result<String, String> {
     let path = std::path::Path::new(&file_path);
@@ -435,9 +876,22 @@ struct ConfigDto {
     current_theme: String,
     locale: String,
     process_filter: String,
+    player_priority: String,
+    sticky_seconds: u64,
     update_source: String,
     auto_check_update: bool,
     autostart: bool,
+    control_token: String,
+    art_cache_max_mb: u64,
+    art_cache_max_age_days: u64,
+    rewrite_rules: Vec<config::RewriteRule>,
+    text_templates: Vec<config::TextTemplate>,
+    file_output_enabled: bool,
+    file_output_dir: String,
+    file_output_files: Vec<config::TextTemplate>,
+    theme_hot_reload: bool,
+    lyrics_dir: String,
+    history_enabled: bool,
 }
 
 #[tauri::command]
@@ -450,14 +904,36 @@ async fn get_config() -> Result<ConfigDto, String> {
         current_theme: config.current_theme.clone(),
         locale: config.locale.clone(),
         process_filter: config.process_filter.clone(),
+        player_priority: config.player_priority.clone(),
+        sticky_seconds: config.sticky_seconds,
         update_source: config.update_source.clone(),
         auto_check_update: config.auto_check_update,
         autostart: config.autostart,
+        control_token: config.control_token.clone(),
+        art_cache_max_mb: config.art_cache_max_mb,
+        art_cache_max_age_days: config.art_cache_max_age_days,
+        rewrite_rules: config.rewrite_rules.clone(),
+        text_templates: config.text_templates.clone(),
+        file_output_enabled: config.file_output_enabled,
+        file_output_dir: config.file_output_dir.clone(),
+        file_output_files: config.file_output_files.clone(),
+        theme_hot_reload: config.theme_hot_reload,
+        lyrics_dir: config.lyrics_dir.clone(),
+        history_enabled: config.history_enabled,
     })
 }
 
 #[tauri::command]
 async fn save_config(config_dto: ConfigDto) -> Result<(), String> {
+    media::filter::ProcessFilter::parse(&config_dto.process_filter)
+        .map_err(|errors| format!("Invalid process filter:\n{}", errors.join("\n")))?;
+    media::rewrite::Rewriter::compile(&config_dto.rewrite_rules)
+        .map_err(|errors| format!("Invalid rewrite rules:\n{}", errors.join("\n")))?;
+    template::validate(&config_dto.text_templates)
+        .map_err(|errors| format!("Invalid text templates:\n{}", errors.join("\n")))?;
+    output::validate(&config_dto.file_output_files)
+        .map_err(|errors| format!("Invalid output files:\n{}", errors.join("\n")))?;
+
     let app_state = APP_STATE.lock().map_err(|e| e.to_string())?;
     let mut config = app_state.config.lock().map_err(|e| e.to_string())?;
 
@@ -466,11 +942,27 @@ async fn save_config(config_dto: ConfigDto) -> Result<(), String> {
     config.current_theme = config_dto.current_theme;
     config.locale = config_dto.locale;
     config.process_filter = config_dto.process_filter;
+    config.player_priority = config_dto.player_priority;
+    config.sticky_seconds = config_dto.sticky_seconds;
     config.update_source = config_dto.update_source;
     config.auto_check_update = config_dto.auto_check_update;
     config.autostart = config_dto.autostart;
-
-    config.save().map_err(|e| e.to_string())
+    config.control_token = config_dto.control_token.trim().to_string();
+    config.art_cache_max_mb = config_dto.art_cache_max_mb;
+    config.art_cache_max_age_days = config_dto.art_cache_max_age_days;
+    media::art_cache::set_limits(config.art_cache_max_mb, config.art_cache_max_age_days);
+    config.rewrite_rules = config_dto.rewrite_rules;
+    config.text_templates = config_dto.text_templates;
+    config.file_output_enabled = config_dto.file_output_enabled;
+    config.file_output_dir = config_dto.file_output_dir.trim().to_string();
+    config.file_output_files = config_dto.file_output_files;
+    config.theme_hot_reload = config_dto.theme_hot_reload;
+    config.lyrics_dir = config_dto.lyrics_dir.trim().to_string();
+    config.history_enabled = config_dto.history_enabled;
+
+    config.save().map_err(|e| e.to_string())?;
+    ws::publish_config(&config);
+    Ok(())
 }
 
 #[tauri::command]
@@ -479,6 +971,162 @@ async fn set_locale(locale: String, app: tauri::AppHandle) -> Result<(), String>
     tray::update_tray_menu_language(&app, &locale)
 }
 
+#[tauri::command]
+async fn media_control(
+    command: media::PlaybackCommand,
+    app_id: Option<String>,
+) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || send_media_command(app_id, &command))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// 改写规则预览的曲目文本
+#[derive(Serialize)]
+struct TrackText {
+    title: String,
+    artist: String,
+    album: String,
+}
+
+impl From<&media::SessionInfo> for TrackText {
+    fn from(info: &media::SessionInfo) -> Self {
+        Self {
+            title: info.title.clone(),
+            artist: info.artist.clone(),
+            album: info.album.clone(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct RewritePreview {
+    app_id: String,
+    app_name: String,
+    before: TrackText,
+    after: TrackText,
+}
+
+/// 预览时使用的示例曲目，未提供时使用当前所有会话
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct RewriteSample {
+    title: String,
+    artist: String,
+    album: String,
+    app_id: String,
+    app_name: String,
+}
+
+fn preview_rewrites(
+    rules: &[config::RewriteRule],
+    sample: Option<RewriteSample>,
+) -> Result<Vec<RewritePreview>, String> {
+    let rewriter = media::rewrite::Rewriter::compile(rules).map_err(|errors| errors.join("\n"))?;
+
+    let samples = match sample {
+        Some(sample) => vec![media::SessionInfo {
+            title: sample.title,
+            artist: sample.artist,
+            album: sample.album,
+            app_id: sample.app_id,
+            app_name: sample.app_name,
+            ..Default::default()
+        }],
+        None => {
+            let session = APP_STATE
+                .lock()
+                .map_err(|e| e.to_string())?
+                .media_session
+                .clone();
+            session.map(|s| s.list_sessions()).unwrap_or_default()
+        }
+    };
+
+    Ok(samples
+        .into_iter()
+        .map(|before| {
+            let mut after = before.clone();
+            rewriter.apply(&mut after);
+            RewritePreview {
+                app_id: before.app_id.clone(),
+                app_name: before.app_name.clone(),
+                before: TrackText::from(&before),
+                after: TrackText::from(&after),
+            }
+        })
+        .collect())
+}
+
+#[tauri::command]
+async fn preview_rewrite_rules(
+    rules: Vec<config::RewriteRule>,
+    sample: Option<RewriteSample>,
+) -> Result<Vec<RewritePreview>, String> {
+    tauri::async_runtime::spawn_blocking(move || preview_rewrites(&rules, sample))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// 用当前曲目渲染文本模板，供设置页预览
+#[tauri::command]
+async fn preview_text_template(template: String) -> Result<String, String> {
+    let template = template::Template::parse(&template)?;
+    let state = APP_STATE
+        .lock()
+        .map_err(|e| e.to_string())?
+        .shared_state
+        .clone()
+        .ok_or("Shared state not initialized")?;
+    let song = state.read().map_err(|e| e.to_string())?;
+    Ok(template.render(&song))
+}
+
+/// 锁定到当前展示的播放器或解除锁定，返回锁定的 app_id
+fn set_player_lock(locked: bool) -> Option<String> {
+    let app_id = if locked {
+        Some(CURRENT_APP_ID.lock().unwrap().clone()).filter(|id| !id.is_empty())
+    } else {
+        None
+    };
+    media::selector::lock_player(app_id.clone());
+    log_info!("Player lock: {:?}", app_id);
+    app_id
+}
+
+#[tauri::command]
+async fn get_player_lock() -> Result<Option<String>, String> {
+    Ok(media::selector::locked_player())
+}
+
+#[tauri::command]
+async fn lock_player(locked: bool, app: tauri::AppHandle) -> Result<Option<String>, String> {
+    let app_id = set_player_lock(locked);
+    tray::refresh_tray_menu(&app);
+    Ok(app_id)
+}
+
+/// 清空封面缓存，返回释放的字节数
+#[tauri::command]
+async fn clear_art_cache() -> Result<u64, String> {
+    let freed = tauri::async_runtime::spawn_blocking(media::clear_album_art_cache)
+        .await
+        .map_err(|e| e.to_string())?;
+    log_info!("Album art cache cleared ({} bytes)", freed);
+    Ok(freed)
+}
+
+/// 最近的播放记录，按结束时间从新到旧，`since` 为 Unix 毫秒
+#[tauri::command]
+async fn get_history(
+    limit: Option<usize>,
+    since: Option<u64>,
+) -> Result<Vec<history::HistoryEntry>, String> {
+    tauri::async_runtime::spawn_blocking(move || history::recent(limit, since))
+        .await
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 async fn get_current_app_id() -> Result<String, String> {
     let display_name = CURRENT_APP_DISPLAY_NAME.lock().map_err(|e| e.to_string())?;
@@ -535,8 +1183,7 @@ fn sync_autostart(enable: bool) -> Result<(), String> {
         }
     }
 
-    #[cfg(not(target_os = "windows"))]
-    {
+    if !cfg!(target_os = "windows") {
         let _ = enable;
         return Err("Auto-start is only supported on Windows".to_string());
     }
@@ -612,6 +1259,22 @@ fn apply_window_rounded_corners(window: &tauri::WebviewWindow) {
     }
 }
 
+/// 解析命令行中的 `--mock-media`、`--mock-media=<path>` 或 `--mock-media <path>`
+fn mock_media_arg(args: &[String]) -> Option<String> {
+    let index = args
+        .iter()
+        .position(|a| a == "--mock-media" || a.starts_with("--mock-media="))?;
+    if let Some(path) = args[index].strip_prefix("--mock-media=") {
+        return Some(path.to_string());
+    }
+    Some(
+        args.get(index + 1)
+            .filter(|next| !next.starts_with("--"))
+            .cloned()
+            .unwrap_or_default(),
+    )
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     logger::init();
@@ -638,29 +1301,59 @@ pub fn run() {
     let config = Arc::new(Mutex::new(config::Config::load().unwrap_or_default()));
 
     config::Config::start_monitoring(config.clone());
+    theme::start_hot_reload(config.clone());
+
+    let mut media_config = config.lock().unwrap().clone();
+    media::art_cache::set_limits(
+        media_config.art_cache_max_mb,
+        media_config.art_cache_max_age_days,
+    );
+
+    // `--mock-media[=脚本路径]` 仅对本次运行生效，不写回配置文件
+    if let Some(script) = mock_media_arg(&args) {
+        log_info!("使用模拟媒体后端");
+        media_config.media_backend = "mock".to_string();
+        if !script.is_empty() {
+            media_config.mock_script = script;
+        }
+    }
 
     let runtime = tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime");
 
     let state: Shared = Arc::default();
+    let sessions: SharedSessions = Arc::default();
     let st = state.clone();
+    let ss = sessions.clone();
 
-    std::thread::spawn(move || media_worker(st));
+    std::thread::spawn(move || media_worker(st, ss, media_config));
 
     let (port, current_theme) = {
         let config_guard = config.lock().unwrap();
         (config_guard.server_port, config_guard.current_theme.clone())
     };
 
+    let theme_manager =
+        theme::ThemeManager::new(&theme_server_path(&current_theme).to_string_lossy());
     let state_for_server = state.clone();
-    let (server_tx, server_handle) =
-        runtime.block_on(async { start_server(state_for_server, port, current_theme).await });
+    let sessions_for_server = sessions.clone();
+    let manager_for_server = theme_manager.clone();
+    let (server_tx, server_handle) = runtime.block_on(async {
+        start_server(
+            state_for_server,
+            sessions_for_server,
+            port,
+            manager_for_server,
+        )
+        .await
+    });
 
     {
         let mut app_state = APP_STATE.lock().unwrap();
         app_state.config = config.clone();
-        app_state.server_tx = Some(server_tx);
         app_state.server_port = port;
         app_state.shared_state = Some(state);
+        app_state.shared_sessions = Some(sessions);
+        app_state.theme_manager = Some(theme_manager);
     }
 
     // 同步开机自启动设置到注册表
@@ -684,10 +1377,18 @@ pub fn run() {
             upload_theme,
             upload_theme_from_bytes,
             delete_theme,
+            reload_overlays,
             get_config,
             save_config,
             set_locale,
             get_current_app_id,
+            media_control,
+            clear_art_cache,
+            get_history,
+            get_player_lock,
+            lock_player,
+            preview_rewrite_rules,
+            preview_text_template,
             updater::check_update,
             updater::start_update,
             set_autostart,
@@ -730,6 +1431,8 @@ pub fn run() {
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 
+    // 发送关闭信号后等待 HTTP 服务器退出；在此之前持有 `server_tx`，避免服务器提前关闭
+    let _ = server_tx.send(());
     runtime.block_on(async {
         let _ = server_handle.await;
     });
@@ -0,0 +1,627 @@
+use crate::{APP_STATE, Shared, Song, log_debug, media, monotonic_ms};
+use dirs::data_dir;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use warp::Filter;
+
+/// 未找到歌词的结果保留时间，之后重新查找（例如刚放入了 .lrc 文件）
+const MISS_TTL: Duration = Duration::from_secs(10);
+
+/// 歌词来源
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LyricsSource {
+    /// 音频文件旁的同名 .lrc
+    File,
+    /// 用户歌词文件夹
+    Folder,
+    /// 音频文件内嵌的歌词：ID3 SYLT / USLT、Vorbis 注释或 MP4 标签
+    Embedded,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LyricWord {
+    pub time_ms: u64,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LyricLine {
+    /// 开始时间，无时间轴的歌词为 0
+    pub time_ms: u64,
+    /// 下一行的开始时间
+    pub end_ms: Option<u64>,
+    pub text: String,
+    /// 逐字时间（增强 LRC 或逐字 SYLT），没有时为空
+    pub words: Vec<LyricWord>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Lyrics {
+    pub source: LyricsSource,
+    /// 是否带时间轴
+    pub synced: bool,
+    pub lines: Vec<LyricLine>,
+}
+
+impl Lyrics {
+    /// 指定位置正在播放的行
+    pub fn line_at(&self, position_ms: u64) -> Option<usize> {
+        if !self.synced {
+            return None;
+        }
+        self.lines
+            .partition_point(|line| line.time_ms <= position_ms)
+            .checked_sub(1)
+    }
+}
+
+/// 解析 LRC 时间标签：`mm:ss`、`mm:ss.xx`、`mm:ss.xxx` 或 `mm:ss:xx`
+fn parse_timestamp(tag: &str) -> Option<u64> {
+    let (minutes, rest) = tag.split_once(':')?;
+    let (seconds, fraction) = match rest.split_once(['.', ':']) {
+        Some((seconds, fraction)) => (seconds, fraction),
+        None => (rest, ""),
+    };
+    let minutes: u64 = minutes.trim().parse().ok()?;
+    let seconds: u64 = seconds.trim().parse().ok()?;
+    if !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let fraction_ms = match fraction.len() {
+        0 => 0,
+        1..=3 => {
+            let value: u64 = fraction.parse().ok()?;
+            value * 10u64.pow(3 - fraction.len() as u32)
+        }
+        _ => fraction[..3].parse().ok()?,
+    };
+    if seconds >= 60 {
+        return None;
+    }
+    // 损坏的文件中分钟数可能极大
+    minutes
+        .checked_mul(60_000)?
+        .checked_add(seconds * 1000 + fraction_ms)
+}
+
+/// 拆分增强 LRC 的逐字时间 `<mm:ss.xx>`，返回去掉标签后的文本
+fn parse_words(text: &str, line_time: u64, offset: i64) -> (String, Vec<LyricWord>) {
+    let mut words = Vec::new();
+    let mut plain = String::new();
+    let mut current: Option<LyricWord> = None;
+    let mut rest = text;
+
+    while let Some(start) = rest.find('<') {
+        let Some(end) = rest[start..].find('>') else {
+            break;
+        };
+        let Some(time) = parse_timestamp(&rest[start + 1..start + end]) else {
+            // 不是时间标签，按普通文本处理
+            let text = &rest[..start + 1];
+            plain.push_str(text);
+            if let Some(word) = &mut current {
+                word.text.push_str(text);
+            }
+            rest = &rest[start + 1..];
+            continue;
+        };
+
+        let text = &rest[..start];
+        plain.push_str(text);
+        match current.take() {
+            Some(mut word) => {
+                word.text.push_str(text);
+                words.push(word);
+            }
+            // 第一个时间标签前的文字从行开始时显示
+            None if !text.trim().is_empty() => words.push(LyricWord {
+                time_ms: line_time,
+                text: text.to_string(),
+            }),
+            None => {}
+        }
+        current = Some(LyricWord {
+            time_ms: apply_offset(time, offset),
+            text: String::new(),
+        });
+        rest = &rest[start + end + 1..];
+    }
+
+    plain.push_str(rest);
+    if let Some(mut word) = current {
+        word.text.push_str(rest);
+        // 行尾的时间标签只标记最后一个字的结束
+        if !word.text.is_empty() {
+            words.push(word);
+        }
+    }
+    (plain.trim().to_string(), words)
+}
+
+/// `[offset:+500]` 表示歌词提前 500 毫秒
+fn apply_offset(time: u64, offset: i64) -> u64 {
+    time.saturating_add_signed(offset.saturating_neg())
+}
+
+/// 解析 LRC 歌词，支持一行多个时间标签与增强 LRC 的逐字时间；
+/// 没有任何时间标签时作为纯文本歌词
+pub fn parse_lrc(content: &str) -> Option<(bool, Vec<LyricLine>)> {
+    let content = content.trim_start_matches('\u{feff}');
+    let offset = content
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("[offset:"))
+        .find_map(|value| value.trim_end_matches(']').trim().parse::<i64>().ok())
+        .unwrap_or(0);
+
+    let mut timed = Vec::new();
+    let mut plain = Vec::new();
+    for raw in content.lines() {
+        let mut rest = raw.trim();
+        let mut times = Vec::new();
+        while let Some(tag) = rest.strip_prefix('[').and_then(|r| r.split_once(']')) {
+            match parse_timestamp(tag.0) {
+                Some(time) => times.push(apply_offset(time, offset)),
+                // `[ar:...]` 等信息标签
+                None if times.is_empty() && tag.0.contains(':') => {}
+                None => break,
+            }
+            rest = tag.1;
+        }
+
+        if times.is_empty() {
+            if !rest.is_empty() {
+                plain.push(rest.to_string());
+            }
+            continue;
+        }
+        for time in times {
+            let (text, words) = parse_words(rest, time, offset);
+            timed.push(LyricLine {
+                time_ms: time,
+                end_ms: None,
+                text,
+                words,
+            });
+        }
+    }
+
+    if !timed.is_empty() {
+        Some((true, finish_lines(timed)))
+    } else if !plain.is_empty() {
+        let lines = plain
+            .into_iter()
+            .map(|text| LyricLine {
+                time_ms: 0,
+                end_ms: None,
+                text,
+                words: Vec::new(),
+            })
+            .collect();
+        Some((false, lines))
+    } else {
+        None
+    }
+}
+
+/// 按时间排序并填写每行的结束时间
+fn finish_lines(mut lines: Vec<LyricLine>) -> Vec<LyricLine> {
+    lines.sort_by_key(|line| line.time_ms);
+    for index in 1..lines.len() {
+        let next = lines[index].time_ms;
+        lines[index - 1].end_ms = Some(next);
+    }
+    lines
+}
+
+/// SYLT 标签的内容：每项是一行，或以换行开头标记新行的逐字内容
+fn parse_sylt(content: &[(u32, String)]) -> Vec<LyricLine> {
+    let per_word = content
+        .iter()
+        .skip(1)
+        .any(|(_, text)| text.starts_with(['\n', '\r']));
+    if !per_word {
+        let lines = content
+            .iter()
+            .map(|(time, text)| LyricLine {
+                time_ms: *time as u64,
+                end_ms: None,
+                text: text.trim().to_string(),
+                words: Vec::new(),
+            })
+            .collect();
+        return finish_lines(lines);
+    }
+
+    let mut lines: Vec<LyricLine> = Vec::new();
+    for (time, text) in content {
+        let time = *time as u64;
+        let word = text.trim_start_matches(['\n', '\r']);
+        if lines.is_empty() || word.len() != text.len() {
+            lines.push(LyricLine {
+                time_ms: time,
+                end_ms: None,
+                text: String::new(),
+                words: Vec::new(),
+            });
+        }
+        let line = lines.last_mut().unwrap();
+        line.text.push_str(word);
+        line.words.push(LyricWord {
+            time_ms: time,
+            text: word.to_string(),
+        });
+    }
+    for line in &mut lines {
+        line.text = line.text.trim().to_string();
+    }
+    finish_lines(lines)
+}
+
+/// 未配置歌词文件夹时使用 `<数据目录>/smtc2web/lyrics`
+pub fn resolve_dir(dir: &str) -> PathBuf {
+    if dir.trim().is_empty() {
+        let mut path = data_dir().unwrap_or_else(|| PathBuf::from("."));
+        path.push("smtc2web");
+        path.push("lyrics");
+        path
+    } else {
+        PathBuf::from(dir.trim())
+    }
+}
+
+fn read_lrc(path: &Path, source: LyricsSource) -> Option<Lyrics> {
+    let data = std::fs::read(path).ok()?;
+    let (synced, lines) = parse_lrc(&String::from_utf8_lossy(&data))?;
+    log_debug!("Lyrics loaded from {}", path.display());
+    Some(Lyrics {
+        source,
+        synced,
+        lines,
+    })
+}
+
+/// 音频文件旁的同名 .lrc
+fn find_beside(audio: &Path) -> Option<Lyrics> {
+    ["lrc", "LRC"]
+        .iter()
+        .map(|ext| audio.with_extension(ext))
+        .find(|path| path.is_file())
+        .and_then(|path| read_lrc(&path, LyricsSource::File))
+}
+
+/// 文件名中不能使用的字符替换为 `_`
+fn sanitize_file_name(name: &str) -> String {
+    name.trim()
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c => c,
+        })
+        .collect()
+}
+
+/// 歌词文件夹中的 `艺术家 - 标题.lrc` 或 `标题.lrc`，不区分大小写
+fn find_in_folder(dir: &Path, song: &Song) -> Option<Lyrics> {
+    let title = sanitize_file_name(&song.title);
+    if title.is_empty() {
+        return None;
+    }
+    let mut candidates = Vec::new();
+    for artist in
+        std::iter::once(song.artist.as_str()).chain(song.artists.iter().map(String::as_str))
+    {
+        let artist = sanitize_file_name(artist);
+        if !artist.is_empty() {
+            candidates.push(format!("{} - {}.lrc", artist, title).to_lowercase());
+        }
+    }
+    candidates.push(format!("{}.lrc", title).to_lowercase());
+
+    let entries: HashMap<String, PathBuf> = std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(Result::ok)
+        .map(|entry| {
+            (
+                entry.file_name().to_string_lossy().to_lowercase(),
+                entry.path(),
+            )
+        })
+        .collect();
+    candidates
+        .iter()
+        .find_map(|name| entries.get(name))
+        .and_then(|path| read_lrc(path, LyricsSource::Folder))
+}
+
+/// 内嵌的 ID3 歌词，优先使用带时间轴的 SYLT
+fn find_id3(audio: &Path) -> Option<Lyrics> {
+    use id3::TagLike;
+    use id3::frame::TimestampFormat;
+
+    let tag = id3::Tag::read_from_path(audio).ok()?;
+    let synced = tag
+        .synchronised_lyrics()
+        // MPEG 帧数无法换算为时间
+        .filter(|sylt| sylt.timestamp_format == TimestampFormat::Ms && !sylt.content.is_empty())
+        .max_by_key(|sylt| sylt.content.len());
+    if let Some(sylt) = synced {
+        return Some(Lyrics {
+            source: LyricsSource::Embedded,
+            synced: true,
+            lines: parse_sylt(&sylt.content),
+        });
+    }
+
+    // USLT 中常直接存放 LRC 文本
+    tag.lyrics().find_map(|uslt| {
+        let (synced, lines) = parse_lrc(&uslt.text)?;
+        Some(Lyrics {
+            source: LyricsSource::Embedded,
+            synced,
+            lines,
+        })
+    })
+}
+
+/// 内嵌歌词：先读 ID3，其次是其它格式标签中的歌词文本（FLAC、Ogg 的 `LYRICS` 等）
+fn find_embedded(audio: &Path) -> Option<Lyrics> {
+    find_id3(audio).or_else(|| {
+        let text = media::tags::embedded_lyrics(audio)?;
+        let (synced, lines) = parse_lrc(&text)?;
+        Some(Lyrics {
+            source: LyricsSource::Embedded,
+            synced,
+            lines,
+        })
+    })
+}
+
+/// 按顺序查找：音频文件旁的 .lrc、歌词文件夹、内嵌标签
+pub fn find_lyrics(song: &Song, lyrics_dir: &Path) -> Option<Lyrics> {
+    let audio = media::local_file_path(&song.url);
+    audio
+        .as_deref()
+        .and_then(find_beside)
+        .or_else(|| find_in_folder(lyrics_dir, song))
+        .or_else(|| audio.as_deref().and_then(find_embedded))
+}
+
+struct CachedLyrics {
+    key: String,
+    lyrics: Option<Arc<Lyrics>>,
+    checked_at: Instant,
+}
+
+static LYRICS_CACHE: Lazy<Mutex<Option<CachedLyrics>>> = Lazy::new(|| Mutex::new(None));
+
+/// 当前曲目的歌词，按曲目缓存
+fn current_lyrics(song: &Song) -> Option<Arc<Lyrics>> {
+    if song.title.is_empty() {
+        return None;
+    }
+    let lyrics_dir = {
+        let config = APP_STATE.lock().ok()?.config.clone();
+        let dir = config.lock().ok()?.lyrics_dir.clone();
+        resolve_dir(&dir)
+    };
+    let key = format!(
+        "{}\n{}\n{}\n{}\n{}",
+        song.title,
+        song.artist,
+        song.album,
+        song.url,
+        lyrics_dir.display()
+    );
+
+    if let Some(cached) = LYRICS_CACHE.lock().unwrap().as_ref()
+        && cached.key == key
+        && (cached.lyrics.is_some() || cached.checked_at.elapsed() < MISS_TTL)
+    {
+        return cached.lyrics.clone();
+    }
+
+    let lyrics = find_lyrics(song, &lyrics_dir).map(Arc::new);
+    *LYRICS_CACHE.lock().unwrap() = Some(CachedLyrics {
+        key,
+        lyrics: lyrics.clone(),
+        checked_at: Instant::now(),
+    });
+    lyrics
+}
+
+/// 按采样时间推算当前播放位置
+fn position_now(song: &Song) -> Option<u64> {
+    let mut position = song.position_ms?;
+    if song.is_playing {
+        let elapsed = monotonic_ms().saturating_sub(song.sampled_at) as f64;
+        position += (elapsed * song.rate.unwrap_or(1.0)) as u64;
+    }
+    Some(match song.duration_ms {
+        Some(duration) => position.min(duration),
+        None => position,
+    })
+}
+
+#[derive(Serialize)]
+struct LyricsResponse {
+    found: bool,
+    source: Option<LyricsSource>,
+    synced: bool,
+    /// 当前行的序号，没有时间轴或尚未到第一行时为 `null`
+    index: Option<usize>,
+    /// 计算 `index` 时使用的播放位置
+    position_ms: Option<u64>,
+    server_time: u64,
+    title: String,
+    artist: String,
+    /// `?lines=0` 时省略，只返回当前行
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lines: Option<Vec<LyricLine>>,
+    current: Option<LyricLine>,
+}
+
+/// `GET /api/lyrics`：当前曲目的完整歌词与正在播放的行，`?lines=0` 时只返回当前行
+pub fn lyrics_route(
+    state: Shared,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("api" / "lyrics")
+        .and(warp::get())
+        .and(warp::query::<HashMap<String, String>>())
+        .and_then(move |query: HashMap<String, String>| {
+            let state = state.clone();
+            async move {
+                let song = state.read().unwrap().clone();
+                // 查找歌词会读取文件，放到阻塞线程中
+                let lyrics = tokio::task::spawn_blocking({
+                    let song = song.clone();
+                    move || current_lyrics(&song)
+                })
+                .await
+                .unwrap_or_default();
+
+                let position_ms = position_now(&song);
+                let index = lyrics
+                    .as_ref()
+                    .zip(position_ms)
+                    .and_then(|(lyrics, position)| lyrics.line_at(position));
+                let with_lines =
+                    !matches!(query.get("lines").map(String::as_str), Some("0" | "false"));
+
+                let response = LyricsResponse {
+                    found: lyrics.is_some(),
+                    source: lyrics.as_ref().map(|l| l.source),
+                    synced: lyrics.as_ref().is_some_and(|l| l.synced),
+                    index,
+                    position_ms,
+                    server_time: monotonic_ms(),
+                    title: song.title,
+                    artist: song.artist,
+                    lines: with_lines
+                        .then(|| lyrics.as_ref().map(|l| l.lines.clone()).unwrap_or_default()),
+                    current: lyrics
+                        .as_ref()
+                        .zip(index)
+                        .map(|(lyrics, index)| lyrics.lines[index].clone()),
+                };
+                Ok::<_, warp::Rejection>(warp::reply::json(&response))
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str) -> (bool, Vec<LyricLine>) {
+        parse_lrc(content).expect("lyrics")
+    }
+
+    fn times(lines: &[LyricLine]) -> Vec<u64> {
+        lines.iter().map(|line| line.time_ms).collect()
+    }
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(parse_timestamp("01:02"), Some(62_000));
+        assert_eq!(parse_timestamp("01:02.5"), Some(62_500));
+        assert_eq!(parse_timestamp("01:02.05"), Some(62_050));
+        assert_eq!(parse_timestamp("01:02.345"), Some(62_345));
+        assert_eq!(parse_timestamp("01:02:34"), Some(62_340));
+        assert_eq!(parse_timestamp("00:60.00"), None);
+        assert_eq!(parse_timestamp("ar:Band"), None);
+        assert_eq!(parse_timestamp("999999999999999999:00"), None);
+        assert_eq!(parse_timestamp("307445734561825:59.999"), None);
+    }
+
+    #[test]
+    fn multiple_timestamps_per_line() {
+        let (synced, lines) = parse(
+            "[ar:Band]\n[00:20.00]Chorus[00:05.00]\n[00:10.00][00:30.00]Chorus\n[00:12.00]Verse",
+        );
+        assert!(synced);
+        assert_eq!(times(&lines), vec![10_000, 12_000, 20_000, 30_000]);
+        assert_eq!(lines[0].text, "Chorus");
+        assert_eq!(lines[3].text, "Chorus");
+        // 文本中的时间标签不作为行时间
+        assert_eq!(lines[2].text, "Chorus[00:05.00]");
+        assert_eq!(lines[0].end_ms, Some(12_000));
+        assert_eq!(lines[3].end_ms, None);
+    }
+
+    #[test]
+    fn offsets() {
+        // 正值表示歌词提前
+        let (_, lines) = parse("[offset:+500]\n[00:01.00]a <00:02.00>b\n[00:00.20]c");
+        assert_eq!(times(&lines), vec![0, 500]);
+        assert_eq!(lines[1].words[1].time_ms, 1500);
+
+        let (_, lines) = parse("[00:01.00]a\n[offset:-250]");
+        assert_eq!(times(&lines), vec![1250]);
+    }
+
+    #[test]
+    fn enhanced_words() {
+        let (_, lines) = parse("[00:01.00]<00:01.00>Hello <00:01.50>world<00:02.00>");
+        assert_eq!(lines[0].text, "Hello world");
+        let words: Vec<_> = lines[0]
+            .words
+            .iter()
+            .map(|word| (word.time_ms, word.text.as_str()))
+            .collect();
+        assert_eq!(words, vec![(1000, "Hello "), (1500, "world")]);
+    }
+
+    #[test]
+    fn plain_text() {
+        let (synced, lines) = parse("\u{feff}First line\n\nSecond line");
+        assert!(!synced);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1].text, "Second line");
+        assert!(parse_lrc("[ar:Band]\n\n").is_none());
+    }
+
+    #[test]
+    fn line_at() {
+        let (synced, lines) = parse("[00:01.00]a\n[00:02.00]b");
+        let lyrics = Lyrics {
+            source: LyricsSource::File,
+            synced,
+            lines,
+        };
+        assert_eq!(lyrics.line_at(500), None);
+        assert_eq!(lyrics.line_at(1000), Some(0));
+        assert_eq!(lyrics.line_at(5000), Some(1));
+    }
+
+    #[test]
+    fn sylt_lines_and_words() {
+        let content = |items: &[(u32, &str)]| -> Vec<(u32, String)> {
+            items
+                .iter()
+                .map(|(time, text)| (*time, text.to_string()))
+                .collect()
+        };
+
+        let lines = parse_sylt(&content(&[(2000, "b"), (1000, " a ")]));
+        assert_eq!(times(&lines), vec![1000, 2000]);
+        assert_eq!(lines[0].text, "a");
+        assert!(lines[0].words.is_empty());
+
+        let lines = parse_sylt(&content(&[
+            (1000, "Hel"),
+            (1200, "lo"),
+            (2000, "\nwor"),
+            (2300, "ld"),
+        ]));
+        assert_eq!(times(&lines), vec![1000, 2000]);
+        assert_eq!(lines[0].text, "Hello");
+        assert_eq!(lines[1].text, "world");
+        assert_eq!(lines[1].words[1].time_ms, 2300);
+        assert_eq!(lines[0].end_ms, Some(2000));
+    }
+}
@@ -0,0 +1,271 @@
+use super::AlbumArt;
+use super::palette::Palette;
+use crate::{log_info, log_warn};
+use dirs::data_dir;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+const INDEX_FILE: &str = "index.json";
+
+/// 默认上限，与 `Config` 默认值一致
+const DEFAULT_MAX_BYTES: u64 = 200 * 1024 * 1024;
+const DEFAULT_MAX_AGE_SECS: u64 = 30 * 24 * 60 * 60;
+
+/// 读取封面只在内存中更新访问时间，每隔这段时间写回一次索引
+const INDEX_FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+static DISK_CACHE: Lazy<Mutex<DiskArtCache>> = Lazy::new(|| {
+    std::thread::spawn(|| {
+        loop {
+            std::thread::sleep(INDEX_FLUSH_INTERVAL);
+            let mut cache = DISK_CACHE.lock().unwrap();
+            if cache.dirty {
+                cache.save_index();
+            }
+        }
+    });
+    Mutex::new(DiskArtCache::open())
+});
+
+#[derive(Default, Serialize, Deserialize)]
+struct CacheIndex {
+    /// 歌曲 ID -> 内容哈希
+    songs: HashMap<String, String>,
+    /// 内容哈希 -> 图片信息
+    images: HashMap<String, ImageEntry>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct ImageEntry {
+    mime: String,
+    size: u64,
+    last_access: u64,
+    /// 封面配色，首次使用时计算
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    palette: Option<Palette>,
+}
+
+/// 磁盘封面缓存，位于数据目录下的 `smtc2web/art_cache`，按最近访问时间淘汰
+struct DiskArtCache {
+    dir: PathBuf,
+    index: CacheIndex,
+    max_bytes: u64,
+    /// 为 0 时不按时间淘汰
+    max_age_secs: u64,
+    /// 索引有未写入磁盘的修改
+    dirty: bool,
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// 内容哈希只允许十六进制字符，防止拼接出缓存目录之外的路径
+fn is_valid_hash(hash: &str) -> bool {
+    !hash.is_empty() && hash.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+impl DiskArtCache {
+    fn open() -> Self {
+        let mut dir = data_dir().unwrap_or_else(|| PathBuf::from("."));
+        dir.push("smtc2web");
+        dir.push("art_cache");
+
+        if let Err(e) = fs::create_dir_all(&dir) {
+            log_warn!("Failed to create album art cache directory: {}", e);
+        }
+
+        let mut index: CacheIndex = fs::read_to_string(dir.join(INDEX_FILE))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+
+        // 丢弃文件已不存在的条目
+        index
+            .images
+            .retain(|hash, _| is_valid_hash(hash) && dir.join(hash).is_file());
+        let CacheIndex { songs, images } = &mut index;
+        songs.retain(|_, hash| images.contains_key(hash));
+
+        let mut cache = Self {
+            dir,
+            index,
+            max_bytes: DEFAULT_MAX_BYTES,
+            max_age_secs: DEFAULT_MAX_AGE_SECS,
+            dirty: false,
+        };
+        cache.evict();
+        cache
+    }
+
+    fn save_index(&mut self) {
+        self.dirty = false;
+        let content = match serde_json::to_string(&self.index) {
+            Ok(content) => content,
+            Err(e) => {
+                log_warn!("Failed to serialize album art cache index: {}", e);
+                return;
+            }
+        };
+        let tmp = self.dir.join(format!("{}.tmp", INDEX_FILE));
+        if let Err(e) =
+            fs::write(&tmp, content).and_then(|_| fs::rename(&tmp, self.dir.join(INDEX_FILE)))
+        {
+            log_warn!("Failed to write album art cache index: {}", e);
+        }
+    }
+
+    fn load(&mut self, hash: &str) -> Option<AlbumArt> {
+        if !is_valid_hash(hash) {
+            return None;
+        }
+        let entry = self.index.images.get_mut(hash)?;
+        let data = fs::read(self.dir.join(hash)).ok()?;
+        entry.last_access = now_secs();
+        self.dirty = true;
+        Some(AlbumArt::new(data, &entry.mime))
+    }
+
+    fn store(&mut self, song_id: &str, art: &AlbumArt) {
+        if !is_valid_hash(&art.hash) {
+            return;
+        }
+        let path = self.dir.join(&art.hash);
+        if !self.index.images.contains_key(&art.hash) || !path.is_file() {
+            // 先写临时文件再重命名，避免读到不完整的图片
+            let tmp = self.dir.join(format!("{}.tmp", art.hash));
+            if let Err(e) = fs::write(&tmp, &art.data).and_then(|_| fs::rename(&tmp, &path)) {
+                log_warn!("Failed to write album art cache file: {}", e);
+                return;
+            }
+        }
+
+        // 同一图片重复写入时保留已计算的配色
+        let palette = self
+            .index
+            .images
+            .get(&art.hash)
+            .and_then(|entry| entry.palette.clone());
+        self.index.images.insert(
+            art.hash.clone(),
+            ImageEntry {
+                mime: art.mime.clone(),
+                size: art.data.len() as u64,
+                last_access: now_secs(),
+                palette,
+            },
+        );
+        self.index
+            .songs
+            .insert(song_id.to_string(), art.hash.clone());
+        self.evict();
+        self.save_index();
+    }
+
+    /// 先淘汰过期图片，再按最近访问时间淘汰到总大小不超过上限
+    fn evict(&mut self) {
+        let now = now_secs();
+        let mut expired: Vec<String> = Vec::new();
+
+        if self.max_age_secs > 0 {
+            expired.extend(
+                self.index
+                    .images
+                    .iter()
+                    .filter(|(_, entry)| now.saturating_sub(entry.last_access) > self.max_age_secs)
+                    .map(|(hash, _)| hash.clone()),
+            );
+        }
+
+        let mut remaining: Vec<(&String, &ImageEntry)> = self
+            .index
+            .images
+            .iter()
+            .filter(|(hash, _)| !expired.contains(hash))
+            .collect();
+        let mut total: u64 = remaining.iter().map(|(_, entry)| entry.size).sum();
+        if total > self.max_bytes {
+            remaining.sort_by_key(|(_, entry)| entry.last_access);
+            for (hash, entry) in remaining {
+                if total <= self.max_bytes {
+                    break;
+                }
+                total -= entry.size;
+                expired.push(hash.clone());
+            }
+        }
+
+        if expired.is_empty() {
+            return;
+        }
+
+        for hash in &expired {
+            self.index.images.remove(hash);
+            let _ = fs::remove_file(self.dir.join(hash));
+        }
+        let CacheIndex { songs, images } = &mut self.index;
+        songs.retain(|_, hash| images.contains_key(hash));
+        self.dirty = true;
+        log_info!("Evicted {} album art cache entries", expired.len());
+    }
+
+    fn clear(&mut self) -> u64 {
+        let freed = self.index.images.values().map(|entry| entry.size).sum();
+        for hash in self.index.images.keys() {
+            let _ = fs::remove_file(self.dir.join(hash));
+        }
+        self.index = CacheIndex::default();
+        self.save_index();
+        freed
+    }
+}
+
+/// 更新缓存上限，`max_age_days` 为 0 表示不按时间淘汰
+pub fn set_limits(max_mb: u64, max_age_days: u64) {
+    let mut cache = DISK_CACHE.lock().unwrap();
+    cache.max_bytes = max_mb.saturating_mul(1024 * 1024);
+    cache.max_age_secs = max_age_days.saturating_mul(24 * 60 * 60);
+    cache.evict();
+    cache.save_index();
+}
+
+pub(super) fn load_song(song_id: &str) -> Option<AlbumArt> {
+    let mut cache = DISK_CACHE.lock().unwrap();
+    let hash = cache.index.songs.get(song_id)?.clone();
+    cache.load(&hash)
+}
+
+pub(super) fn load_hash(hash: &str) -> Option<AlbumArt> {
+    DISK_CACHE.lock().unwrap().load(hash)
+}
+
+pub(super) fn store(song_id: &str, art: &AlbumArt) {
+    DISK_CACHE.lock().unwrap().store(song_id, art);
+}
+
+pub(super) fn load_palette(hash: &str) -> Option<Palette> {
+    let cache = DISK_CACHE.lock().unwrap();
+    cache.index.images.get(hash)?.palette.clone()
+}
+
+/// 配色保存在图片的索引条目中，图片不在磁盘缓存中时不保存
+pub(super) fn store_palette(hash: &str, palette: &Palette) {
+    let mut cache = DISK_CACHE.lock().unwrap();
+    if let Some(entry) = cache.index.images.get_mut(hash) {
+        entry.palette = Some(palette.clone());
+        cache.save_index();
+    }
+}
+
+/// 清空磁盘缓存，返回释放的字节数
+pub(super) fn clear() -> u64 {
+    DISK_CACHE.lock().unwrap().clear()
+}
@@ -0,0 +1,238 @@
+use super::AlbumArt;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::sync::{Arc, Mutex};
+
+/// 输出图片的最大边长
+const MAX_SIZE: u32 = 2048;
+const MAX_BLUR: u32 = 100;
+const JPEG_QUALITY: u8 = 85;
+
+/// 内存中最多保留的变体数
+const VARIANT_CACHE_SIZE: usize = 32;
+
+/// 生成的变体，`hash` 为 `ArtVariant::key`
+static VARIANT_CACHE: Lazy<Mutex<Vec<AlbumArt>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+/// 缩放方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Fit {
+    /// 裁剪为 `size` × `size` 的正方形
+    Cover,
+    /// 保持比例缩放到 `size` × `size` 以内
+    #[default]
+    Contain,
+}
+
+/// `/api/art/{hash}?size=&fit=&blur=` 请求的封面变体
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ArtVariant {
+    /// 不会超过原图尺寸；未指定时 `contain` 保持原尺寸，`cover` 取短边
+    pub size: Option<u32>,
+    pub fit: Fit,
+    /// 高斯模糊的 sigma（像素），按缩放后的尺寸计算，0 为不模糊
+    pub blur: u32,
+}
+
+impl ArtVariant {
+    /// 解析查询参数，超出范围的数值限制在范围内
+    pub fn from_query(query: &HashMap<String, String>) -> Result<Self, String> {
+        let number = |name: &str| {
+            query
+                .get(name)
+                .map(|value| {
+                    value
+                        .trim()
+                        .parse::<u32>()
+                        .map_err(|_| format!("Invalid `{}`: {}", name, value))
+                })
+                .transpose()
+        };
+        let fit = match query.get("fit").map(|fit| fit.trim().to_ascii_lowercase()) {
+            None => Fit::default(),
+            Some(fit) if fit == "cover" => Fit::Cover,
+            Some(fit) if fit == "contain" => Fit::Contain,
+            Some(fit) => {
+                return Err(format!(
+                    "Invalid `fit`: {} (expected cover or contain)",
+                    fit
+                ));
+            }
+        };
+        Ok(Self {
+            size: number("size")?
+                .filter(|size| *size > 0)
+                .map(|size| size.min(MAX_SIZE)),
+            fit,
+            blur: number("blur")?.unwrap_or(0).min(MAX_BLUR),
+        })
+    }
+
+    /// 不需要处理，直接返回原图
+    pub fn is_original(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// 变体的标识，由原图哈希与参数决定，用作缓存键与 ETag
+    pub fn key(&self, hash: &str) -> String {
+        let fit = match self.fit {
+            Fit::Cover => "cover",
+            Fit::Contain => "contain",
+        };
+        format!("{}-{}-{}-{}", hash, self.size.unwrap_or(0), fit, self.blur)
+    }
+}
+
+/// 有透明通道时输出 PNG，否则输出 JPEG
+fn encode(image: &DynamicImage) -> Option<(Vec<u8>, &'static str)> {
+    let mut data = Vec::new();
+    if image.color().has_alpha() {
+        image
+            .write_to(&mut Cursor::new(&mut data), ImageFormat::Png)
+            .ok()?;
+        Some((data, "image/png"))
+    } else {
+        image
+            .to_rgb8()
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut data, JPEG_QUALITY))
+            .ok()?;
+        Some((data, "image/jpeg"))
+    }
+}
+
+/// 生成封面变体，结果缓存在内存中；无法解码时返回 `None`
+pub fn render(art: &AlbumArt, variant: ArtVariant) -> Option<AlbumArt> {
+    let key = variant.key(&art.hash);
+    {
+        let mut cache = VARIANT_CACHE.lock().unwrap();
+        if let Some(index) = cache.iter().position(|cached| cached.hash == key) {
+            // 移到末尾，淘汰时优先移除最久未使用的
+            let cached = cache.remove(index);
+            cache.push(cached.clone());
+            return Some(cached);
+        }
+    }
+
+    let image = image::load_from_memory(&art.data).ok()?;
+    let (width, height) = (image.width(), image.height());
+    // 只缩小不放大，放大交给浏览器即可
+    let mut image = match variant.fit {
+        Fit::Contain => match variant.size {
+            Some(size) if size < width.max(height) => {
+                image.resize(size, size, FilterType::CatmullRom)
+            }
+            _ => image,
+        },
+        Fit::Cover => {
+            let size = variant
+                .size
+                .map_or(width.min(height), |size| size.min(width.min(height)));
+            image.resize_to_fill(size, size, FilterType::CatmullRom)
+        }
+    };
+    if variant.blur > 0 {
+        image = image.fast_blur(variant.blur as f32);
+    }
+
+    let (data, mime) = encode(&image)?;
+    let rendered = AlbumArt {
+        hash: key,
+        mime: mime.to_string(),
+        data: Arc::from(data),
+    };
+    let mut cache = VARIANT_CACHE.lock().unwrap();
+    if cache.len() >= VARIANT_CACHE_SIZE {
+        cache.remove(0);
+    }
+    cache.push(rendered.clone());
+    Some(rendered)
+}
+
+pub(super) fn clear() {
+    VARIANT_CACHE.lock().unwrap().clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    fn parse(pairs: &[(&str, &str)]) -> Result<ArtVariant, String> {
+        ArtVariant::from_query(&query(pairs))
+    }
+
+    #[test]
+    fn defaults_to_original() {
+        let variant = parse(&[]).unwrap();
+        assert!(variant.is_original());
+        // 0 与未指定相同
+        assert!(
+            parse(&[("size", "0"), ("blur", "0")])
+                .unwrap()
+                .is_original()
+        );
+    }
+
+    #[test]
+    fn clamps_numbers() {
+        let variant = parse(&[("size", " 99999 "), ("blur", "500")]).unwrap();
+        assert_eq!(variant.size, Some(MAX_SIZE));
+        assert_eq!(variant.blur, MAX_BLUR);
+        assert_eq!(parse(&[("size", "300")]).unwrap().size, Some(300));
+    }
+
+    #[test]
+    fn rejects_invalid_numbers() {
+        for (name, value) in [("size", "-1"), ("size", "big"), ("blur", "1.5")] {
+            let error = parse(&[(name, value)]).unwrap_err();
+            assert!(error.contains(name), "{}", error);
+        }
+    }
+
+    #[test]
+    fn parses_fit() {
+        assert_eq!(parse(&[("fit", "Cover")]).unwrap().fit, Fit::Cover);
+        assert_eq!(parse(&[("fit", "contain")]).unwrap().fit, Fit::Contain);
+        let error = parse(&[("fit", "fill")]).unwrap_err();
+        assert!(error.contains("fill"), "{}", error);
+    }
+
+    #[test]
+    fn key_includes_parameters() {
+        let variant = parse(&[("size", "64"), ("fit", "cover"), ("blur", "2")]).unwrap();
+        assert_eq!(variant.key("abc"), "abc-64-cover-2");
+        assert_ne!(variant.key("abc"), ArtVariant::default().key("abc"));
+    }
+
+    #[test]
+    fn renders_cover_square() {
+        let image = image::RgbImage::from_pixel(40, 20, image::Rgb([200, 10, 10]));
+        let mut data = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut data), ImageFormat::Png)
+            .unwrap();
+        let art = AlbumArt::new(data, "image/png");
+
+        let variant = parse(&[("size", "16"), ("fit", "cover")]).unwrap();
+        let rendered = render(&art, variant).unwrap();
+        assert_eq!(rendered.hash, variant.key(&art.hash));
+        assert_eq!(rendered.mime, "image/jpeg");
+        let output = image::load_from_memory(&rendered.data).unwrap();
+        assert_eq!((output.width(), output.height()), (16, 16));
+
+        // 只缩小不放大
+        let variant = parse(&[("size", "100")]).unwrap();
+        let output = image::load_from_memory(&render(&art, variant).unwrap().data).unwrap();
+        assert_eq!((output.width(), output.height()), (40, 20));
+    }
+}
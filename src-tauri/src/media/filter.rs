@@ -0,0 +1,259 @@
+use regex::{Regex, RegexBuilder};
+
+/// 进程过滤规则，每行一条：
+///
+/// ```text
+/// # 以 # 开头的行为注释
+/// *                 # 匹配全部（空白过滤器同样匹配全部）
+/// spotify           # app_id 或显示名称包含 "spotify"
+/// name:Fire*fox     # 显示名称中包含匹配通配符（* 与 ?）的片段
+/// id:re:^org\.mpris\.MediaPlayer2\.(vlc|mpv)
+/// !firefox          # 排除
+/// \#hash            # 包含 "#hash"，行首的 `\#` 表示字面的 #
+/// ```
+///
+/// 匹配均不区分大小写。存在包含规则时必须命中其中之一；命中任意排除规则即被过滤。
+/// 只有排除规则时，其余会话全部匹配。
+///
+/// 旧版本每行都是子串匹配，为保持兼容，通配符同样在任意位置匹配
+/// （`spotify*` 与 `spotify` 等价），需要整体匹配时使用 `re:^...$`。
+#[derive(Debug, Clone, Default)]
+pub struct ProcessFilter {
+    includes: Vec<Rule>,
+    excludes: Vec<Rule>,
+    match_all: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Scope {
+    Any,
+    Id,
+    Name,
+}
+
+#[derive(Debug, Clone)]
+enum Pattern {
+    Contains(String),
+    Regex(Regex),
+}
+
+/// 单条规则（不含 `!` 前缀），玩家优先级列表也使用同样的语法
+#[derive(Debug, Clone)]
+pub(crate) struct Rule {
+    scope: Scope,
+    pattern: Pattern,
+}
+
+impl Rule {
+    pub(crate) fn parse(line: &str) -> Result<Self, String> {
+        // `\#` 转义行首的 #，避免被当作注释
+        let line = line
+            .strip_prefix('\\')
+            .filter(|rest| rest.starts_with('#'))
+            .unwrap_or(line);
+        let (scope, rest) = if let Some(rest) = strip_prefix_ci(line, "id:") {
+            (Scope::Id, rest)
+        } else if let Some(rest) = strip_prefix_ci(line, "name:") {
+            (Scope::Name, rest)
+        } else {
+            (Scope::Any, line)
+        };
+
+        let pattern = if let Some(expr) = strip_prefix_ci(rest, "re:") {
+            Pattern::Regex(compile(expr)?)
+        } else if rest.contains(['*', '?']) {
+            Pattern::Regex(compile(&glob_to_regex(rest))?)
+        } else {
+            Pattern::Contains(rest.to_lowercase())
+        };
+
+        if matches!(&pattern, Pattern::Contains(text) if text.is_empty()) {
+            return Err("empty pattern".to_string());
+        }
+
+        Ok(Rule { scope, pattern })
+    }
+
+    pub(crate) fn matches(&self, id: &str, name: &str) -> bool {
+        let test = |value: &str| match &self.pattern {
+            Pattern::Contains(text) => value.to_lowercase().contains(text.as_str()),
+            Pattern::Regex(re) => re.is_match(value),
+        };
+        match self.scope {
+            Scope::Any => test(id) || test(name),
+            Scope::Id => test(id),
+            Scope::Name => test(name),
+        }
+    }
+}
+
+fn strip_prefix_ci<'a>(text: &'a str, prefix: &str) -> Option<&'a str> {
+    let head = text.get(..prefix.len())?;
+    head.eq_ignore_ascii_case(prefix)
+        .then(|| text[prefix.len()..].trim_start())
+}
+
+fn compile(expr: &str) -> Result<Regex, String> {
+    RegexBuilder::new(expr)
+        .case_insensitive(true)
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+/// 通配符在任意位置匹配：`*` 任意长度，`?` 单个字符
+fn glob_to_regex(glob: &str) -> String {
+    let mut expr = String::new();
+    for c in glob.chars() {
+        match c {
+            '*' => expr.push_str(".*"),
+            '?' => expr.push('.'),
+            c => expr.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    expr
+}
+
+impl ProcessFilter {
+    /// 严格解析，返回所有出错行（行号从 1 开始）
+    pub fn parse(text: &str) -> Result<Self, Vec<String>> {
+        let mut filter = ProcessFilter::default();
+        let mut errors = Vec::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line == "*" {
+                filter.match_all = true;
+                continue;
+            }
+
+            let (exclude, body) = match line.strip_prefix('!') {
+                Some(body) => (true, body.trim_start()),
+                None => (false, line),
+            };
+
+            match Rule::parse(body) {
+                Ok(rule) if exclude => filter.excludes.push(rule),
+                Ok(rule) => filter.includes.push(rule),
+                Err(e) => errors.push(format!("line {}: `{}`: {}", index + 1, line, e)),
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(filter)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// 宽松解析，忽略无效的行，供媒体后端使用（保存配置时已做过校验）
+    pub fn parse_lossy(text: &str) -> Self {
+        match Self::parse(text) {
+            Ok(filter) => filter,
+            Err(errors) => {
+                for error in &errors {
+                    crate::log_warn!("Ignoring invalid process filter rule: {}", error);
+                }
+                let valid: Vec<&str> = text
+                    .lines()
+                    .filter(|line| Self::parse(line).is_ok())
+                    .collect();
+                Self::parse(&valid.join("\n")).unwrap_or_default()
+            }
+        }
+    }
+
+    pub fn matches(&self, id: &str, name: &str) -> bool {
+        if self.excludes.iter().any(|rule| rule.matches(id, name)) {
+            return false;
+        }
+        self.match_all
+            || self.includes.is_empty()
+            || self.includes.iter().any(|rule| rule.matches(id, name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(filter: &str, id: &str, name: &str) -> bool {
+        ProcessFilter::parse(filter).unwrap().matches(id, name)
+    }
+
+    #[test]
+    fn empty_and_star_match_everything() {
+        assert!(matches("", "org.mpris.MediaPlayer2.vlc", "VLC"));
+        assert!(matches("*", "org.mpris.MediaPlayer2.vlc", "VLC"));
+        assert!(matches("# only a comment", "anything", "Anything"));
+    }
+
+    #[test]
+    fn plain_text_is_case_insensitive_substring() {
+        assert!(matches("spotify", "Spotify.exe", ""));
+        assert!(matches("SPOTIFY", "", "spotify"));
+        assert!(!matches("spotify", "firefox", "Firefox"));
+    }
+
+    #[test]
+    fn glob_matches_anywhere() {
+        // 与旧版本的子串匹配保持一致
+        assert!(matches("spotify*", "com.Spotify.Client", ""));
+        assert!(matches("fire*fox", "", "Mozilla Firefox"));
+        assert!(matches("v?c", "org.mpris.MediaPlayer2.vlc", ""));
+        assert!(!matches("v?c", "org.mpris.MediaPlayer2.mpv", ""));
+        // 通配符之外的字符按字面匹配
+        assert!(!matches("a.c*", "abc", ""));
+    }
+
+    #[test]
+    fn scoped_rules() {
+        assert!(matches("name:vlc", "", "VLC"));
+        assert!(!matches("name:vlc", "org.mpris.MediaPlayer2.vlc", "Media"));
+        assert!(matches("id:vlc", "org.mpris.MediaPlayer2.vlc", "Media"));
+        assert!(!matches("ID:vlc", "", "VLC"));
+    }
+
+    #[test]
+    fn regex_rules() {
+        let filter = r"id:re:^org\.mpris\.MediaPlayer2\.(vlc|mpv)$";
+        assert!(matches(filter, "org.mpris.MediaPlayer2.VLC", ""));
+        assert!(matches(filter, "org.mpris.MediaPlayer2.mpv", ""));
+        assert!(!matches(filter, "org.mpris.MediaPlayer2.vlc.instance1", ""));
+    }
+
+    #[test]
+    fn negation() {
+        // 只有排除规则时其余会话全部匹配
+        assert!(!matches("!firefox", "firefox", ""));
+        assert!(matches("!firefox", "spotify", ""));
+        // 排除优先于包含
+        let filter = "*\n!name:chrom*";
+        assert!(matches(filter, "spotify", "Spotify"));
+        assert!(!matches(filter, "chromium", "Chromium"));
+    }
+
+    #[test]
+    fn comments_and_escaped_hash() {
+        let filter = "# players\nspotify\n  # vlc";
+        assert!(matches(filter, "spotify", ""));
+        assert!(!matches(filter, "vlc", ""));
+        assert!(matches("\\#radio", "", "#Radio"));
+        assert!(!matches("\\#radio", "", "Radio"));
+    }
+
+    #[test]
+    fn invalid_lines_are_reported() {
+        let errors = ProcessFilter::parse("spotify\nre:(\nname:").unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].starts_with("line 2: `re:(`"));
+        assert!(errors[1].starts_with("line 3: `name:`"));
+
+        // 宽松解析保留有效的行
+        let filter = ProcessFilter::parse_lossy("spotify\nre:(");
+        assert!(filter.matches("spotify", ""));
+        assert!(!filter.matches("vlc", ""));
+    }
+}
@@ -0,0 +1,650 @@
+use super::filter::ProcessFilter;
+use super::{
+    AlbumArt, LoopStatus, MediaSession, PlaybackCommand, SessionInfo, generate_song_id,
+    get_cached_album_art, set_cached_album_art, sniff_image_mime, split_artists,
+};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// 模拟播放列表脚本（TOML 或 JSON），例如：
+///
+/// ```toml
+/// speed = 10.0        # 时间倍速
+/// rate = 1.0          # 播放器自身的播放速率，报告的速率为 rate × speed
+/// loop = true
+/// app_name = "Mock Player"
+///
+/// [[items]]
+/// title = "First Song"
+/// artist = "Someone"
+/// album = "Demo"
+/// duration = 185
+/// art = "covers/first.jpg"   # 相对于脚本所在目录
+/// pauses = [{ at = 30, duration = 5 }]
+///
+/// [[items]]
+/// gap = 10            # 无播放器的空档（秒）
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MockScript {
+    pub speed: f64,
+    /// 模拟播放器的播放速率，报告时乘以 `speed`
+    pub rate: f64,
+    #[serde(rename = "loop")]
+    pub looping: bool,
+    pub app_id: String,
+    pub app_name: String,
+    pub items: Vec<MockItem>,
+}
+
+impl Default for MockScript {
+    fn default() -> Self {
+        Self {
+            speed: 1.0,
+            rate: 1.0,
+            looping: true,
+            app_id: "smtc2web.mock".to_string(),
+            app_name: "Mock Player".to_string(),
+            items: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum MockItem {
+    Gap { gap: f64 },
+    Track(Box<MockTrack>),
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct MockTrack {
+    pub title: String,
+    /// 多位艺术家以 ", " 分隔
+    #[serde(default)]
+    pub artist: String,
+    #[serde(default)]
+    pub album: String,
+    #[serde(default)]
+    pub album_artist: String,
+    #[serde(default)]
+    pub track_number: Option<u32>,
+    #[serde(default)]
+    pub track_count: Option<u32>,
+    #[serde(default)]
+    pub genres: Vec<String>,
+    #[serde(default)]
+    pub year: Option<i32>,
+    #[serde(default)]
+    pub url: String,
+    /// 时长（秒）
+    pub duration: f64,
+    #[serde(default)]
+    pub art: Option<PathBuf>,
+    #[serde(default)]
+    pub pauses: Vec<MockPause>,
+}
+
+/// 播放到 `at` 秒时暂停 `duration` 秒
+#[derive(Debug, Clone, Deserialize)]
+pub struct MockPause {
+    pub at: f64,
+    pub duration: f64,
+}
+
+impl MockItem {
+    /// 在时间轴上占用的长度（秒）
+    fn span(&self) -> f64 {
+        match self {
+            MockItem::Gap { gap } => gap.max(0.0),
+            MockItem::Track(track) => {
+                let paused: f64 = track.pauses.iter().map(|p| p.duration.max(0.0)).sum();
+                track.duration.max(0.0) + paused
+            }
+        }
+    }
+}
+
+impl MockTrack {
+    /// 将曲目内的时间轴偏移换算为 (播放位置, 是否在播放)
+    fn position_at(&self, offset: f64) -> (f64, bool) {
+        let mut offset = offset;
+        let mut pauses: Vec<&MockPause> = self.pauses.iter().collect();
+        pauses.sort_by(|a, b| a.at.total_cmp(&b.at));
+        for pause in pauses {
+            if offset < pause.at {
+                break;
+            }
+            if offset < pause.at + pause.duration {
+                return (pause.at, false);
+            }
+            offset -= pause.duration;
+        }
+        (offset.min(self.duration), true)
+    }
+
+    /// `position_at` 的逆运算，用于跳转
+    fn offset_of(&self, position: f64) -> f64 {
+        let paused: f64 = self
+            .pauses
+            .iter()
+            .filter(|p| p.at < position)
+            .map(|p| p.duration.max(0.0))
+            .sum();
+        position.clamp(0.0, self.duration) + paused
+    }
+}
+
+/// 虚拟时钟，支持暂停与跳转
+struct MockClock {
+    base: f64,
+    started_at: Instant,
+    paused: bool,
+    volume: f64,
+}
+
+impl MockClock {
+    fn now(&self, speed: f64) -> f64 {
+        if self.paused {
+            self.base
+        } else {
+            self.base + self.started_at.elapsed().as_secs_f64() * speed
+        }
+    }
+
+    fn set(&mut self, time: f64) {
+        self.base = time.max(0.0);
+        self.started_at = Instant::now();
+    }
+}
+
+pub struct MockSession {
+    script: MockScript,
+    base_dir: PathBuf,
+    process_filter: ProcessFilter,
+    clock: Mutex<MockClock>,
+}
+
+impl MockSession {
+    /// 从脚本文件加载，扩展名为 `.json` 时按 JSON 解析，否则按 TOML 解析
+    pub fn load(path: &Path, process_filter: &str) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read mock script {}: {}", path.display(), e))?;
+        let script: MockScript = if path.extension().is_some_and(|ext| ext == "json") {
+            serde_json::from_str(&content).map_err(|e| format!("Invalid mock script: {}", e))?
+        } else {
+            toml::from_str(&content).map_err(|e| format!("Invalid mock script: {}", e))?
+        };
+        let base_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        Self::from_script(script, base_dir, process_filter)
+    }
+
+    pub fn from_script(
+        script: MockScript,
+        base_dir: PathBuf,
+        process_filter: &str,
+    ) -> Result<Self, String> {
+        if !script
+            .items
+            .iter()
+            .any(|item| matches!(item, MockItem::Track(_)))
+        {
+            return Err("Mock script contains no tracks".to_string());
+        }
+        if script.speed <= 0.0 {
+            return Err("Mock script speed must be positive".to_string());
+        }
+        if script.rate <= 0.0 {
+            return Err("Mock script rate must be positive".to_string());
+        }
+
+        crate::log_info!(
+            "Mock media session: {} items, speed x{}",
+            script.items.len(),
+            script.speed
+        );
+
+        Ok(MockSession {
+            script,
+            base_dir,
+            process_filter: ProcessFilter::parse_lossy(process_filter),
+            clock: Mutex::new(MockClock {
+                base: 0.0,
+                started_at: Instant::now(),
+                paused: false,
+                volume: 1.0,
+            }),
+        })
+    }
+
+    /// 内置演示播放列表
+    fn demo_script() -> MockScript {
+        let track =
+            |number: u32, title: &str, artist: &str, duration: f64, pauses: Vec<MockPause>| {
+                MockItem::Track(Box::new(MockTrack {
+                    title: title.to_string(),
+                    artist: artist.to_string(),
+                    album: "smtc2web Demo".to_string(),
+                    album_artist: "Various Artists".to_string(),
+                    track_number: Some(number),
+                    track_count: Some(3),
+                    genres: vec!["Test".to_string()],
+                    year: Some(2024),
+                    duration,
+                    pauses,
+                    ..MockTrack::default()
+                }))
+            };
+        MockScript {
+            items: vec![
+                track(1, "Morning Commute", "Mock Ensemble", 184.0, Vec::new()),
+                track(
+                    2,
+                    "Pause For Thought",
+                    "The Placeholders",
+                    211.0,
+                    vec![MockPause {
+                        at: 60.0,
+                        duration: 8.0,
+                    }],
+                ),
+                MockItem::Gap { gap: 5.0 },
+                track(
+                    3,
+                    "Long Form Podcast Episode",
+                    "Test Fixtures Radio",
+                    3725.0,
+                    Vec::new(),
+                ),
+            ],
+            ..MockScript::default()
+        }
+    }
+
+    fn cycle_length(&self) -> f64 {
+        self.script.items.iter().map(MockItem::span).sum()
+    }
+
+    /// 时间轴上每一项的起点
+    fn item_starts(&self) -> Vec<f64> {
+        let mut start = 0.0;
+        self.script
+            .items
+            .iter()
+            .map(|item| {
+                let current = start;
+                start += item.span();
+                current
+            })
+            .collect()
+    }
+
+    /// 计算给定时间点的 (项序号, 项内偏移)，播放结束返回 None
+    fn locate(&self, time: f64) -> Option<(usize, f64)> {
+        let cycle = self.cycle_length();
+        if cycle <= 0.0 {
+            return None;
+        }
+        let time = if self.script.looping {
+            time % cycle
+        } else if time >= cycle {
+            return None;
+        } else {
+            time
+        };
+
+        let starts = self.item_starts();
+        self.script
+            .items
+            .iter()
+            .zip(starts)
+            .enumerate()
+            .find(|(_, (item, start))| time < start + item.span())
+            .map(|(index, (_, start))| (index, time - start))
+    }
+
+    fn current_time(&self) -> f64 {
+        self.clock
+            .lock()
+            .map(|clock| clock.now(self.script.speed))
+            .unwrap_or(0.0)
+    }
+
+    fn current_track(&self) -> Option<(usize, &MockTrack, f64, bool)> {
+        let clock_paused = self.clock.lock().map(|c| c.paused).unwrap_or(false);
+        let (index, offset) = self.locate(self.current_time())?;
+        match &self.script.items[index] {
+            MockItem::Track(track) => {
+                let (position, playing) = track.position_at(offset);
+                Some((index, track, position, playing && !clock_paused))
+            }
+            MockItem::Gap { .. } => None,
+        }
+    }
+
+    /// 跳到 `index` 之后（或之前）的第一首曲目的起点
+    fn jump_to_track(&self, from: usize, forward: bool) {
+        let count = self.script.items.len();
+        let starts = self.item_starts();
+        let cycle_base = {
+            let cycle = self.cycle_length();
+            let now = self.current_time();
+            if cycle > 0.0 { now - now % cycle } else { 0.0 }
+        };
+
+        let mut index = from;
+        for _ in 0..count {
+            index = if forward {
+                (index + 1) % count
+            } else {
+                (index + count - 1) % count
+            };
+            if matches!(self.script.items[index], MockItem::Track(_)) {
+                break;
+            }
+        }
+
+        if let Ok(mut clock) = self.clock.lock() {
+            clock.set(cycle_base + starts[index]);
+        }
+    }
+}
+
+impl MediaSession for MockSession {
+    fn new(process_filter: &str) -> Result<Self, String>
+    where
+        Self: Sized,
+    {
+        Self::from_script(Self::demo_script(), PathBuf::new(), process_filter)
+    }
+
+    fn poll_current(&self) -> Option<SessionInfo> {
+        if !self
+            .process_filter
+            .matches(&self.script.app_id, &self.script.app_name)
+        {
+            return None;
+        }
+
+        let (_, track, position, is_playing) = self.current_track()?;
+        let volume = self.clock.lock().map(|clock| clock.volume).ok();
+        Some(SessionInfo {
+            title: track.title.clone(),
+            artist: track.artist.clone(),
+            artists: split_artists(&track.artist),
+            album: track.album.clone(),
+            album_artist: track.album_artist.clone(),
+            track_number: track.track_number,
+            track_count: track.track_count,
+            genres: track.genres.clone(),
+            year: track.year,
+            url: track.url.clone(),
+            shuffle: Some(false),
+            loop_status: Some(if self.script.looping {
+                LoopStatus::Playlist
+            } else {
+                LoopStatus::None
+            }),
+            volume,
+            rate: Some(self.script.rate * self.script.speed),
+            is_playing,
+            position_ms: (position * 1000.0) as u64,
+            duration_ms: (track.duration * 1000.0) as u64,
+            app_id: self.script.app_id.clone(),
+            app_name: self.script.app_name.clone(),
+            ..Default::default()
+        })
+    }
+
+    fn list_sessions(&self) -> Vec<SessionInfo> {
+        self.poll_current().into_iter().collect()
+    }
+
+    fn get_album_art(
+        &self,
+        _app_id: &str,
+        artist: &str,
+        title: &str,
+        album: &str,
+    ) -> Option<AlbumArt> {
+        let song_id = generate_song_id(title, artist, album);
+
+        if let Some(cached) = get_cached_album_art(&song_id) {
+            return Some(cached);
+        }
+
+        // 元数据可能已被改写规则修改，直接取当前曲目的封面
+        let (_, track, _, _) = self.current_track()?;
+        let path = self.base_dir.join(track.art.as_ref()?);
+        let data = std::fs::read(&path).ok()?;
+
+        let mime = sniff_image_mime(&data)
+            .map(str::to_string)
+            .unwrap_or_else(|| {
+                mime_guess::from_path(&path)
+                    .first_or_octet_stream()
+                    .to_string()
+            });
+        let art = AlbumArt::new(data, &mime);
+
+        set_cached_album_art(&song_id, art.clone());
+
+        Some(art)
+    }
+
+    fn control(&self, app_id: &str, command: &PlaybackCommand) -> Result<(), String> {
+        if app_id != self.script.app_id {
+            return Err(format!("Session not found: {}", app_id));
+        }
+
+        let speed = self.script.speed;
+        match command {
+            PlaybackCommand::Play | PlaybackCommand::Pause | PlaybackCommand::PlayPause => {
+                let mut clock = self.clock.lock().map_err(|e| e.to_string())?;
+                let now = clock.now(speed);
+                clock.paused = match command {
+                    PlaybackCommand::Play => false,
+                    PlaybackCommand::Pause => true,
+                    _ => !clock.paused,
+                };
+                clock.set(now);
+            }
+            PlaybackCommand::Next | PlaybackCommand::Previous => {
+                let (index, _) = self
+                    .locate(self.current_time())
+                    .ok_or("Playlist has ended")?;
+                self.jump_to_track(index, matches!(command, PlaybackCommand::Next));
+            }
+            PlaybackCommand::Seek { position_ms } => {
+                let (_, track, _, _) = self.current_track().ok_or("No track is playing")?;
+                let (_, offset) = self
+                    .locate(self.current_time())
+                    .ok_or("Playlist has ended")?;
+                let target = track.offset_of(*position_ms as f64 / 1000.0);
+                let mut clock = self.clock.lock().map_err(|e| e.to_string())?;
+                let now = clock.now(speed);
+                clock.set(now - offset + target);
+            }
+            PlaybackCommand::SetVolume { volume } => {
+                let mut clock = self.clock.lock().map_err(|e| e.to_string())?;
+                clock.volume = volume.clamp(0.0, 1.0);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn track(title: &str, duration: f64, pauses: Vec<MockPause>) -> MockItem {
+        MockItem::Track(Box::new(MockTrack {
+            title: title.to_string(),
+            duration,
+            pauses,
+            ..MockTrack::default()
+        }))
+    }
+
+    /// 曲目 A（10 秒）、5 秒空档、曲目 B（100 秒，30 秒处暂停 5 秒）
+    fn mock(speed: f64, looping: bool) -> MockSession {
+        let script = MockScript {
+            speed,
+            looping,
+            items: vec![
+                track("A", 10.0, Vec::new()),
+                MockItem::Gap { gap: 5.0 },
+                track(
+                    "B",
+                    100.0,
+                    vec![MockPause {
+                        at: 30.0,
+                        duration: 5.0,
+                    }],
+                ),
+            ],
+            ..MockScript::default()
+        };
+        MockSession::from_script(script, PathBuf::new(), "").unwrap()
+    }
+
+    fn set_time(session: &MockSession, time: f64) {
+        session.clock.lock().unwrap().set(time);
+    }
+
+    /// 当前曲目与整秒的播放位置
+    fn now_playing(session: &MockSession) -> Option<(String, u64, bool)> {
+        session
+            .poll_current()
+            .map(|info| (info.title, info.position_ms / 1000, info.is_playing))
+    }
+
+    #[test]
+    fn load_script_files() {
+        let dir = std::env::temp_dir();
+        let toml_path = dir.join(format!("smtc2web-mock-{}.toml", std::process::id()));
+        std::fs::write(
+            &toml_path,
+            "speed = 2.0\nloop = false\n\n[[items]]\ntitle = \"A\"\nduration = 10\n\n[[items]]\ngap = 3\n",
+        )
+        .unwrap();
+        let session = MockSession::load(&toml_path, "").unwrap();
+        assert_eq!(session.script.speed, 2.0);
+        assert!(!session.script.looping);
+        assert!(matches!(session.script.items[1], MockItem::Gap { gap } if gap == 3.0));
+        assert_eq!(session.base_dir, dir);
+        std::fs::remove_file(&toml_path).unwrap();
+
+        let json_path = dir.join(format!("smtc2web-mock-{}.json", std::process::id()));
+        std::fs::write(
+            &json_path,
+            r#"{"items": [{"title": "A", "duration": 10, "pauses": [{"at": 1, "duration": 2}]}]}"#,
+        )
+        .unwrap();
+        let session = MockSession::load(&json_path, "").unwrap();
+        assert_eq!(session.cycle_length(), 12.0);
+        std::fs::remove_file(&json_path).unwrap();
+    }
+
+    #[test]
+    fn rejects_invalid_scripts() {
+        let only_gap = MockScript {
+            items: vec![MockItem::Gap { gap: 5.0 }],
+            ..MockScript::default()
+        };
+        assert!(MockSession::from_script(only_gap, PathBuf::new(), "").is_err());
+        let stopped = MockScript {
+            speed: 0.0,
+            items: vec![track("A", 10.0, Vec::new())],
+            ..MockScript::default()
+        };
+        assert!(MockSession::from_script(stopped, PathBuf::new(), "").is_err());
+    }
+
+    #[test]
+    fn pauses_shift_positions() {
+        let MockItem::Track(track) = &mock(1.0, true).script.items[2] else {
+            unreachable!();
+        };
+        assert_eq!(track.position_at(10.0), (10.0, true));
+        assert_eq!(track.position_at(32.0), (30.0, false));
+        assert_eq!(track.position_at(40.0), (35.0, true));
+        assert_eq!(track.position_at(500.0), (100.0, true));
+        assert_eq!(track.offset_of(10.0), 10.0);
+        assert_eq!(track.offset_of(35.0), 40.0);
+        assert_eq!(track.position_at(track.offset_of(35.0)), (35.0, true));
+    }
+
+    #[test]
+    fn timeline_with_gaps() {
+        let session = mock(1.0, true);
+        set_time(&session, 3.0);
+        assert_eq!(now_playing(&session), Some(("A".to_string(), 3, true)));
+        // 空档中没有播放器
+        set_time(&session, 12.0);
+        assert_eq!(now_playing(&session), None);
+        set_time(&session, 47.0);
+        assert_eq!(now_playing(&session), Some(("B".to_string(), 30, false)));
+        // 循环回到开头
+        set_time(&session, 123.0);
+        assert_eq!(now_playing(&session), Some(("A".to_string(), 3, true)));
+
+        let session = mock(1.0, false);
+        set_time(&session, 123.0);
+        assert_eq!(now_playing(&session), None);
+    }
+
+    #[test]
+    fn speed_scales_time_and_rate() {
+        let session = mock(10.0, true);
+        {
+            let mut clock = session.clock.lock().unwrap();
+            clock.base = 15.0;
+            clock.started_at = Instant::now() - Duration::from_millis(1500);
+        }
+        let info = session.poll_current().unwrap();
+        assert_eq!((info.title.as_str(), info.position_ms / 1000), ("B", 15));
+        assert_eq!(info.rate, Some(10.0));
+    }
+
+    #[test]
+    fn playback_commands() {
+        let session = mock(1.0, true);
+        let app_id = session.script.app_id.clone();
+
+        set_time(&session, 3.0);
+        session.control(&app_id, &PlaybackCommand::Next).unwrap();
+        assert_eq!(now_playing(&session), Some(("B".to_string(), 0, true)));
+        session.control(&app_id, &PlaybackCommand::Next).unwrap();
+        assert_eq!(now_playing(&session), Some(("A".to_string(), 0, true)));
+        session
+            .control(&app_id, &PlaybackCommand::Previous)
+            .unwrap();
+        assert_eq!(now_playing(&session), Some(("B".to_string(), 0, true)));
+
+        // 跳转位置越过暂停点时计入暂停时长
+        session
+            .control(
+                &app_id,
+                &PlaybackCommand::Seek {
+                    position_ms: 40_000,
+                },
+            )
+            .unwrap();
+        assert_eq!(now_playing(&session), Some(("B".to_string(), 40, true)));
+
+        session.control(&app_id, &PlaybackCommand::Pause).unwrap();
+        assert_eq!(now_playing(&session), Some(("B".to_string(), 40, false)));
+        session
+            .control(&app_id, &PlaybackCommand::PlayPause)
+            .unwrap();
+        assert_eq!(now_playing(&session), Some(("B".to_string(), 40, true)));
+
+        assert!(session.control("other", &PlaybackCommand::Play).is_err());
+    }
+}
@@ -1,73 +1,364 @@
-use std::collections::HashMap;
-use std::sync::Mutex;
+use crate::log_debug;
 use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// 内存中最多保留的歌曲封面数
+const ALBUM_ART_CACHE_SIZE: usize = 30;
+
+/// 没有封面的歌曲在这段时间内不再重新获取
+const MISSING_ART_RETRY: Duration = Duration::from_secs(10);
+
+/// 专辑封面缓存：歌曲 ID -> (内容哈希, 写入时间)，内容哈希 -> 图片、配色
+#[derive(Default)]
+struct AlbumArtCache {
+    songs: HashMap<String, (String, u64)>,
+    images: HashMap<String, AlbumArt>,
+    /// 无法取色的图片记为 `None`，避免每次轮询都重新解码
+    palettes: HashMap<String, Option<palette::Palette>>,
+    /// 没有封面的歌曲 ID -> 记录时间，避免每次轮询都读取标签、下载图片
+    missing: HashMap<String, Instant>,
+}
+
+static ALBUM_ART_CACHE: Lazy<Mutex<AlbumArtCache>> =
+    Lazy::new(|| Mutex::new(AlbumArtCache::default()));
+
+/// 专辑封面图片，以内容的 SHA-256 作为标识
+#[derive(Debug, Clone)]
+pub struct AlbumArt {
+    pub hash: String,
+    pub mime: String,
+    pub data: Arc<[u8]>,
+}
+
+impl AlbumArt {
+    pub fn new(data: Vec<u8>, mime: &str) -> Self {
+        use sha2::{Digest, Sha256};
+        let hash = format!("{:x}", Sha256::digest(&data));
+        Self {
+            hash,
+            mime: mime.to_string(),
+            data: Arc::from(data),
+        }
+    }
+
+    /// `/api/art/{hash}` 地址，写入 `Song.album_art`
+    pub fn url(&self) -> String {
+        format!("/api/art/{}", self.hash)
+    }
 
-static ALBUM_ART_CACHE: Lazy<Mutex<HashMap<String, (String, u64)>>> =
-    Lazy::new(|| Mutex::new(HashMap::new()));
+    pub fn to_data_uri(&self) -> String {
+        use base64::{Engine, engine::general_purpose::STANDARD};
+        format!("data:{};base64,{}", self.mime, STANDARD.encode(&self.data))
+    }
+}
 
 #[derive(Debug, Clone, Default)]
 pub struct SessionInfo {
     pub title: String,
+    /// 以 ", " 连接的艺术家，保留用于兼容
     pub artist: String,
+    pub artists: Vec<String>,
     pub album: String,
+    pub album_artist: String,
+    pub track_number: Option<u32>,
+    /// 专辑曲目总数，MPRIS 不提供
+    pub track_count: Option<u32>,
+    pub disc_number: Option<u32>,
+    pub genres: Vec<String>,
+    pub year: Option<i32>,
+    /// 曲目来源地址（`xesam:url`）
+    pub url: String,
+    /// 播放器内部的曲目 ID（`mpris:trackid`）
+    pub track_id: String,
+    /// 用户评分，0.0 ~ 1.0
+    pub rating: Option<f64>,
+    pub shuffle: Option<bool>,
+    pub loop_status: Option<LoopStatus>,
+    /// 播放器音量，0.0 ~ 1.0
+    pub volume: Option<f64>,
+    /// 播放速率，1.0 为正常速度
+    pub rate: Option<f64>,
     pub is_playing: bool,
-    pub position_secs: u64,
-    pub duration_secs: u64,
+    /// 采样时刻的播放位置（毫秒）
+    pub position_ms: u64,
+    /// 曲目时长（毫秒），未知时为 0
+    pub duration_ms: u64,
     pub app_id: String,
     pub app_name: String,
 }
 
-pub trait MediaSession: Send + 'static {
+/// 循环模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoopStatus {
+    None,
+    Track,
+    Playlist,
+}
+
+/// 将 `SessionInfo.artist` 形式的字符串拆回艺术家列表
+pub fn split_artists(artist: &str) -> Vec<String> {
+    artist
+        .split(", ")
+        .map(str::trim)
+        .filter(|artist| !artist.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// 将 `file://` 地址转换为本地路径，其它地址返回 `None`
+pub fn local_file_path(url: &str) -> Option<std::path::PathBuf> {
+    let rest = url.strip_prefix("file://")?;
+    // 跳过主机名部分，只支持本机（空主机名或 localhost）
+    let rest = rest.strip_prefix("localhost").unwrap_or(rest);
+    if !rest.starts_with('/') {
+        return None;
+    }
+    let path = percent_encoding::percent_decode_str(rest)
+        .decode_utf8()
+        .ok()?;
+    // Windows 下形如 `/C:/Music/a.mp3`
+    let path = match path.as_bytes() {
+        [b'/', drive, b':', ..] if cfg!(windows) && drive.is_ascii_alphabetic() => &path[1..],
+        _ => &path,
+    };
+    Some(std::path::PathBuf::from(path))
+}
+
+/// 播放控制命令，HTTP 接口与 Tauri 命令共用
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum PlaybackCommand {
+    Play,
+    Pause,
+    PlayPause,
+    Next,
+    Previous,
+    /// 跳转到绝对位置（毫秒）
+    Seek {
+        position_ms: u64,
+    },
+    /// 设置音量，范围 0.0 ~ 1.0
+    SetVolume {
+        volume: f64,
+    },
+}
+
+pub trait MediaSession: Send + Sync + 'static {
     fn new(process_filter: &str) -> Result<Self, String>
     where
         Self: Sized;
     fn poll_current(&self) -> Option<SessionInfo>;
-    fn get_album_art_base64(&self, artist: &str, title: &str, album: &str) -> Option<String>;
+    /// 列出所有通过进程过滤器的媒体会话，按优先级排序
+    fn list_sessions(&self) -> Vec<SessionInfo>;
+    /// 等待媒体状态变化，最多阻塞 `timeout`。
+    /// 默认实现直接休眠（轮询式后端），事件驱动的后端可在状态变化时提前返回。
+    fn wait_for_change(&self, timeout: Duration) {
+        std::thread::sleep(timeout);
+    }
+    /// 获取 `app_id` 对应会话当前曲目的封面
+    fn get_album_art(
+        &self,
+        app_id: &str,
+        artist: &str,
+        title: &str,
+        album: &str,
+    ) -> Option<AlbumArt>;
+    /// 向 `app_id` 对应的会话发送播放控制命令
+    fn control(&self, app_id: &str, command: &PlaybackCommand) -> Result<(), String>;
+}
+
+/// 根据文件头识别常见图片格式
+pub(crate) fn sniff_image_mime(data: &[u8]) -> Option<&'static str> {
+    match data {
+        [0xFF, 0xD8, 0xFF, ..] => Some("image/jpeg"),
+        [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, ..] => Some("image/png"),
+        [b'G', b'I', b'F', b'8', ..] => Some("image/gif"),
+        [
+            b'R',
+            b'I',
+            b'F',
+            b'F',
+            _,
+            _,
+            _,
+            _,
+            b'W',
+            b'E',
+            b'B',
+            b'P',
+            ..,
+        ] => Some("image/webp"),
+        [b'B', b'M', ..] => Some("image/bmp"),
+        [
+            _,
+            _,
+            _,
+            _,
+            b'f',
+            b't',
+            b'y',
+            b'p',
+            b'a',
+            b'v',
+            b'i',
+            b'f',
+            ..,
+        ] => Some("image/avif"),
+        _ => None,
+    }
 }
 
 pub(crate) fn generate_song_id(title: &str, artist: &str, album: &str) -> String {
     format!("{}|{}|{}", title, artist, album)
 }
 
-pub(crate) fn matches_process_filter(filter: &str, id: &str, name: &str) -> bool {
-    let filter = filter.trim();
-    if filter == "*" || filter.is_empty() {
-        return true;
-    }
-    let id_lower = id.to_lowercase();
-    let name_lower = name.to_lowercase();
-    filter.lines().any(|line| {
-        let pattern = line.trim().to_lowercase();
-        if pattern.is_empty() {
-            return false;
+/// 先查内存缓存，未命中时从磁盘缓存加载
+pub(crate) fn get_cached_album_art(song_id: &str) -> Option<AlbumArt> {
+    {
+        let cache = ALBUM_ART_CACHE.lock().unwrap();
+        if let Some((hash, _)) = cache.songs.get(song_id) {
+            return cache.images.get(hash).cloned();
         }
-        id_lower.contains(&pattern) || name_lower.contains(&pattern)
-    })
+        if cache.missing.contains_key(song_id) {
+            return None;
+        }
+    }
+
+    let art = art_cache::load_song(song_id)?;
+    remember_album_art(song_id, art.clone());
+    Some(art)
+}
+
+/// 按内容哈希查找封面，供 `/api/art/{hash}` 使用
+pub(crate) fn get_album_art_by_hash(hash: &str) -> Option<AlbumArt> {
+    get_memory_album_art(hash).or_else(|| art_cache::load_hash(hash))
+}
+
+/// 只查内存缓存，可在异步处理函数中调用。当前曲目与各会话的封面都已在内存中
+pub(crate) fn get_memory_album_art(hash: &str) -> Option<AlbumArt> {
+    ALBUM_ART_CACHE.lock().unwrap().images.get(hash).cloned()
+}
+
+/// 最近获取过封面但没有结果，`MISSING_ART_RETRY` 后才重新获取
+pub(crate) fn album_art_missing(song_id: &str) -> bool {
+    let mut cache = ALBUM_ART_CACHE.lock().unwrap();
+    cache
+        .missing
+        .retain(|_, at| at.elapsed() < MISSING_ART_RETRY);
+    cache.missing.contains_key(song_id)
+}
+
+pub(crate) fn mark_album_art_missing(song_id: &str) {
+    ALBUM_ART_CACHE
+        .lock()
+        .unwrap()
+        .missing
+        .insert(song_id.to_string(), Instant::now());
+}
+
+pub(crate) fn set_cached_album_art(song_id: &str, art: AlbumArt) {
+    art_cache::store(song_id, &art);
+    remember_album_art(song_id, art);
+}
+
+/// 封面的配色，每张图片只计算一次，结果与封面一同缓存在磁盘上
+pub(crate) fn album_palette(art: &AlbumArt) -> Option<palette::Palette> {
+    if let Some(palette) = ALBUM_ART_CACHE.lock().unwrap().palettes.get(&art.hash) {
+        return palette.clone();
+    }
+
+    let palette = art_cache::load_palette(&art.hash).or_else(|| {
+        let palette = palette::extract(&art.data)?;
+        art_cache::store_palette(&art.hash, &palette);
+        Some(palette)
+    });
+    if palette.is_none() {
+        log_debug!("Failed to extract palette from album art {}", art.hash);
+    }
+    ALBUM_ART_CACHE
+        .lock()
+        .unwrap()
+        .palettes
+        .insert(art.hash.clone(), palette.clone());
+    palette
 }
 
-pub(crate) fn get_cached_album_art(song_id: &str) -> Option<String> {
-    let cache = ALBUM_ART_CACHE.lock().unwrap();
-    cache.get(song_id).map(|(art, _)| art.clone())
+/// 清空内存与磁盘中的封面缓存，返回磁盘上释放的字节数
+pub(crate) fn clear_album_art_cache() -> u64 {
+    *ALBUM_ART_CACHE.lock().unwrap() = AlbumArtCache::default();
+    art_variant::clear();
+    art_cache::clear()
 }
 
-pub(crate) fn set_cached_album_art(song_id: &str, art: String) {
+fn remember_album_art(song_id: &str, art: AlbumArt) {
     let mut cache = ALBUM_ART_CACHE.lock().unwrap();
     let timestamp = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
         .unwrap()
         .as_secs();
-    cache.insert(song_id.to_string(), (art, timestamp));
+    cache.missing.remove(song_id);
+    cache
+        .songs
+        .insert(song_id.to_string(), (art.hash.clone(), timestamp));
+    cache.images.insert(art.hash.clone(), art);
 
-    if cache.len() > 30 {
-        let mut entries: Vec<_> = cache.iter().collect();
-        entries.sort_by(|a, b| b.1 .1.cmp(&a.1 .1));
-        let to_remove: Vec<String> = entries.iter().skip(30).map(|(k, _)| (*k).clone()).collect();
+    if cache.songs.len() > ALBUM_ART_CACHE_SIZE {
+        let mut entries: Vec<_> = cache.songs.iter().collect();
+        entries.sort_by_key(|(_, (_, timestamp))| std::cmp::Reverse(*timestamp));
+        let to_remove: Vec<String> = entries
+            .iter()
+            .skip(ALBUM_ART_CACHE_SIZE)
+            .map(|(k, _)| (*k).clone())
+            .collect();
         for key in to_remove {
-            cache.remove(key.as_str());
+            cache.songs.remove(key.as_str());
         }
+        // 移除不再被任何歌曲引用的图片
+        let AlbumArtCache {
+            songs,
+            images,
+            palettes,
+            ..
+        } = &mut *cache;
+        images.retain(|hash, _| songs.values().any(|(h, _)| h == hash));
+        palettes.retain(|hash, _| images.contains_key(hash));
     }
 }
 
+/// 按配置创建媒体会话。`backend` 为 "mock" 时使用模拟后端，
+/// `mock_script` 为空则播放内置的演示列表；其余情况使用系统后端。
+pub fn create_session(
+    backend: &str,
+    mock_script: &str,
+    process_filter: &str,
+) -> Result<Arc<dyn MediaSession>, String> {
+    if backend.eq_ignore_ascii_case("mock") {
+        let session = if mock_script.trim().is_empty() {
+            mock::MockSession::new(process_filter)?
+        } else {
+            mock::MockSession::load(Path::new(mock_script.trim()), process_filter)?
+        };
+        return Ok(Arc::new(session));
+    }
+
+    Ok(Arc::new(PlatformSession::new(process_filter)?))
+}
+
+pub mod art_cache;
+pub mod art_variant;
+pub mod filter;
+mod mock;
+pub mod palette;
+mod remote_art;
+pub mod rewrite;
+pub mod selector;
+pub mod tags;
+
 #[cfg(target_os = "windows")]
 mod smtc;
 #[cfg(target_os = "windows")]
@@ -1,61 +1,315 @@
-use super::{generate_song_id, get_cached_album_art, matches_process_filter, set_cached_album_art, MediaSession, SessionInfo};
-use mpris::PlaybackStatus;
-use std::sync::Mutex;
+use super::filter::ProcessFilter;
+use super::{
+    AlbumArt, LoopStatus, MediaSession, PlaybackCommand, SessionInfo, generate_song_id,
+    get_cached_album_art, remote_art, set_cached_album_art, sniff_image_mime,
+};
+use dbus::Message;
+use dbus::arg::{ArgType, PropMap, RefArg, Variant};
+use dbus::blocking::Connection;
+use dbus::blocking::stdintf::org_freedesktop_dbus::Properties;
+use dbus::message::MessageType;
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
 
-pub struct MprisSession {
-    process_filter: String,
-    last_art_url: Mutex<Option<String>>,
+const MPRIS_PREFIX: &str = "org.mpris.MediaPlayer2.";
+const MPRIS_PATH: &str = "/org/mpris/MediaPlayer2";
+const ROOT_INTERFACE: &str = "org.mpris.MediaPlayer2";
+const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+const DBUS_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// 监听的 D-Bus 信号：属性变化、跳转、播放器上下线
+const MATCH_RULES: &[&str] = &[
+    "type='signal',interface='org.freedesktop.DBus.Properties',member='PropertiesChanged',path='/org/mpris/MediaPlayer2'",
+    "type='signal',interface='org.mpris.MediaPlayer2.Player',member='Seeked',path='/org/mpris/MediaPlayer2'",
+    "type='signal',sender='org.freedesktop.DBus',interface='org.freedesktop.DBus',member='NameOwnerChanged',arg0namespace='org.mpris.MediaPlayer2'",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PlaybackStatus {
+    Playing,
+    Paused,
+    Stopped,
 }
 
-impl MprisSession {
-    fn find_player(&self) -> Result<mpris::Player, String> {
-        let finder = mpris::PlayerFinder::new()
-            .map_err(|e| format!("Failed to create PlayerFinder: {:?}", e))?;
-
-        let players: Vec<_> = finder
-            .iter_players()
-            .map_err(|e| format!("Failed to iterate players: {:?}", e))?
-            .filter_map(|p| p.ok())
-            .collect();
+impl PlaybackStatus {
+    fn parse(value: &str) -> Self {
+        match value {
+            "Playing" => PlaybackStatus::Playing,
+            "Paused" => PlaybackStatus::Paused,
+            _ => PlaybackStatus::Stopped,
+        }
+    }
 
-        crate::log_info!("MPRIS2: {} players found", players.len());
+    /// 选择播放器时的优先级：Playing > Paused > Stopped
+    fn rank(self) -> u8 {
+        match self {
+            PlaybackStatus::Playing => 0,
+            PlaybackStatus::Paused => 1,
+            PlaybackStatus::Stopped => 2,
+        }
+    }
+}
 
-        if players.is_empty() {
-            return Err("No players found".to_string());
+/// 单个播放器的缓存状态，由信号增量更新
+#[derive(Debug, Clone)]
+struct PlayerState {
+    bus_name: String,
+    identity: String,
+    status: PlaybackStatus,
+    title: String,
+    artists: Vec<String>,
+    album: String,
+    album_artists: Vec<String>,
+    track_number: Option<u32>,
+    disc_number: Option<u32>,
+    genres: Vec<String>,
+    year: Option<i32>,
+    url: String,
+    rating: Option<f64>,
+    art_url: String,
+    track_id: String,
+    length_us: i64,
+    shuffle: Option<bool>,
+    loop_status: Option<LoopStatus>,
+    volume: Option<f64>,
+    rate: f64,
+    position_us: i64,
+    position_at: Instant,
+}
+
+impl PlayerState {
+    fn new(bus_name: &str) -> Self {
+        Self {
+            bus_name: bus_name.to_string(),
+            identity: String::new(),
+            status: PlaybackStatus::Stopped,
+            title: String::new(),
+            artists: Vec::new(),
+            album: String::new(),
+            album_artists: Vec::new(),
+            track_number: None,
+            disc_number: None,
+            genres: Vec::new(),
+            year: None,
+            url: String::new(),
+            rating: None,
+            art_url: String::new(),
+            track_id: String::new(),
+            length_us: 0,
+            shuffle: None,
+            loop_status: None,
+            volume: None,
+            rate: 1.0,
+            position_us: 0,
+            position_at: Instant::now(),
+        }
+    }
+
+    /// 根据上次同步的位置推算当前播放位置
+    fn position_now_us(&self) -> i64 {
+        let mut position = self.position_us;
+        if self.status == PlaybackStatus::Playing {
+            position += (self.position_at.elapsed().as_micros() as f64 * self.rate) as i64;
+        }
+        if self.length_us > 0 {
+            position = position.min(self.length_us);
         }
+        position.max(0)
+    }
 
-        let mut sorted_players = players;
-        sorted_players.sort_by(|a, b| {
-            let a_status = a.get_playback_status().unwrap_or(PlaybackStatus::Stopped);
-            let b_status = b.get_playback_status().unwrap_or(PlaybackStatus::Stopped);
-            
-            match (a_status, b_status) {
-                (PlaybackStatus::Playing, PlaybackStatus::Playing) => std::cmp::Ordering::Equal,
-                (PlaybackStatus::Playing, _) => std::cmp::Ordering::Less,
-                (_, PlaybackStatus::Playing) => std::cmp::Ordering::Greater,
-                (PlaybackStatus::Paused, PlaybackStatus::Paused) => std::cmp::Ordering::Equal,
-                (PlaybackStatus::Paused, _) => std::cmp::Ordering::Less,
-                (_, PlaybackStatus::Paused) => std::cmp::Ordering::Greater,
-                _ => std::cmp::Ordering::Equal,
+    /// 日志中的曲目与播放状态
+    fn summary(&self) -> String {
+        format!(
+            "{} by {} [{}]",
+            self.title,
+            self.artists.join(", "),
+            if self.status == PlaybackStatus::Playing {
+                "playing"
+            } else {
+                "paused"
             }
-        });
+        )
+    }
+
+    fn set_position(&mut self, position_us: i64) {
+        self.position_us = position_us;
+        self.position_at = Instant::now();
+    }
 
-        for player in sorted_players {
-            if matches_process_filter(&self.process_filter, &player.bus_name(), &player.identity()) {
-                crate::log_debug!("MPRIS2: Selected player: {}", player.bus_name());
-                return Ok(player);
+    /// 应用 Player 接口的属性，返回是否需要重新同步播放位置
+    fn apply_player_props(&mut self, props: &PropMap) -> bool {
+        let mut needs_resync = false;
+        for (key, value) in props {
+            let value = unwrap_variant(&*value.0);
+            match key.as_str() {
+                "PlaybackStatus" => {
+                    if let Some(status) = value.as_str() {
+                        // 先冻结推算位置，避免状态切换时进度跳变
+                        let position = self.position_now_us();
+                        self.set_position(position);
+                        self.status = PlaybackStatus::parse(status);
+                        needs_resync = true;
+                    }
+                }
+                "Metadata" => {
+                    self.apply_metadata(value);
+                    needs_resync = true;
+                }
+                "Position" => {
+                    if let Some(position) = as_int(value) {
+                        self.set_position(position);
+                    }
+                }
+                "Rate" => {
+                    if let Some(rate) = value.as_f64().filter(|rate| *rate > 0.0) {
+                        // 按旧速率冻结推算位置后再切换
+                        let position = self.position_now_us();
+                        self.set_position(position);
+                        self.rate = rate;
+                    }
+                }
+                "Shuffle" => self.shuffle = value.as_i64().map(|v| v != 0),
+                "LoopStatus" => {
+                    self.loop_status = value.as_str().and_then(|status| match status {
+                        "None" => Some(LoopStatus::None),
+                        "Track" => Some(LoopStatus::Track),
+                        "Playlist" => Some(LoopStatus::Playlist),
+                        _ => None,
+                    });
+                }
+                "Volume" => self.volume = value.as_f64().map(|volume| volume.clamp(0.0, 1.0)),
+                _ => {}
             }
         }
+        needs_resync
+    }
+
+    fn apply_metadata(&mut self, metadata: &dyn RefArg) {
+        self.title.clear();
+        self.artists.clear();
+        self.album.clear();
+        self.album_artists.clear();
+        self.track_number = None;
+        self.disc_number = None;
+        self.genres.clear();
+        self.year = None;
+        self.url.clear();
+        self.rating = None;
+        self.art_url.clear();
+        self.track_id.clear();
+        self.length_us = 0;
 
-        Err("No matching player found".to_string())
+        let Some(mut iter) = metadata.as_iter() else {
+            return;
+        };
+        while let (Some(key), Some(value)) = (iter.next(), iter.next()) {
+            let Some(key) = key.as_str() else {
+                continue;
+            };
+            let value = unwrap_variant(value);
+            match key {
+                "xesam:title" => self.title = value.as_str().unwrap_or_default().to_string(),
+                "xesam:artist" => self.artists = string_list(value),
+                "xesam:album" => self.album = value.as_str().unwrap_or_default().to_string(),
+                "xesam:albumArtist" => self.album_artists = string_list(value),
+                "xesam:trackNumber" => self.track_number = as_positive(value),
+                "xesam:discNumber" => self.disc_number = as_positive(value),
+                "xesam:genre" => self.genres = string_list(value),
+                "xesam:contentCreated" => self.year = value.as_str().and_then(parse_year),
+                "xesam:url" => self.url = value.as_str().unwrap_or_default().to_string(),
+                "xesam:userRating" => {
+                    self.rating = value.as_f64().map(|rating| rating.clamp(0.0, 1.0));
+                }
+                "mpris:artUrl" => self.art_url = value.as_str().unwrap_or_default().to_string(),
+                "mpris:trackid" => self.track_id = value.as_str().unwrap_or_default().to_string(),
+                "mpris:length" => self.length_us = as_int(value).unwrap_or(0),
+                _ => {}
+            }
+        }
     }
+}
 
-    fn format_artist_list(artists: &[&str]) -> String {
+/// 所有播放器的缓存，由监听线程写入，`poll_current` 只读
+#[derive(Default)]
+struct Players {
+    /// bus name -> 状态
+    by_name: HashMap<String, PlayerState>,
+    /// unique name (":1.42") -> bus name，信号的 sender 是 unique name
+    owners: HashMap<String, String>,
+    /// 每次状态变化递增，用于唤醒 `wait_for_change`
+    generation: u64,
+}
+
+struct PlayerCache {
+    players: Mutex<Players>,
+    changed: Condvar,
+}
+
+impl PlayerCache {
+    fn update<F: FnOnce(&mut Players)>(&self, f: F) {
+        let mut players = self.players.lock().unwrap();
+        f(&mut players);
+        players.generation = players.generation.wrapping_add(1);
+        self.changed.notify_all();
+    }
+}
+
+pub struct MprisSession {
+    process_filter: ProcessFilter,
+    cache: Arc<PlayerCache>,
+    seen_generation: Mutex<u64>,
+    /// 控制命令使用的连接，首次发送命令时建立（监听连接由监听线程独占）
+    control_conn: Mutex<Option<Connection>>,
+}
+
+impl MprisSession {
+    fn format_artist_list(artists: &[String]) -> String {
         artists.join(", ")
     }
 
-    fn extract_display_name(player: &mpris::Player) -> String {
-        player.identity().to_string()
+    /// 按播放状态排序后列出匹配进程过滤器的播放器
+    fn matching_players<'a>(&self, players: &'a Players) -> Vec<&'a PlayerState> {
+        let mut candidates: Vec<&PlayerState> = players
+            .by_name
+            .values()
+            .filter(|p| self.process_filter.matches(&p.bus_name, &p.identity))
+            .collect();
+        candidates.sort_by(|a, b| {
+            a.status
+                .rank()
+                .cmp(&b.status.rank())
+                .then_with(|| a.bus_name.cmp(&b.bus_name))
+        });
+        candidates
+    }
+
+    fn to_session_info(player: &PlayerState) -> SessionInfo {
+        SessionInfo {
+            title: player.title.clone(),
+            artist: Self::format_artist_list(&player.artists),
+            artists: player.artists.clone(),
+            album: player.album.clone(),
+            album_artist: Self::format_artist_list(&player.album_artists),
+            track_number: player.track_number,
+            track_count: None,
+            disc_number: player.disc_number,
+            genres: player.genres.clone(),
+            year: player.year,
+            url: player.url.clone(),
+            track_id: player.track_id.clone(),
+            rating: player.rating,
+            shuffle: player.shuffle,
+            loop_status: player.loop_status,
+            volume: player.volume,
+            rate: Some(player.rate),
+            is_playing: player.status == PlaybackStatus::Playing,
+            position_ms: (player.position_now_us() / 1000) as u64,
+            duration_ms: (player.length_us / 1000).max(0) as u64,
+            app_id: player.bus_name.clone(),
+            app_name: player.identity.clone(),
+        }
     }
 }
 
@@ -64,93 +318,543 @@ impl MediaSession for MprisSession {
     where
         Self: Sized,
     {
+        let cache = Arc::new(PlayerCache {
+            players: Mutex::new(Players::default()),
+            changed: Condvar::new(),
+        });
+
+        // 连接在监听线程内创建并独占，这里只等待连接结果
+        let (ready_tx, ready_rx) = std::sync::mpsc::sync_channel::<Result<(), String>>(1);
+        let listener_cache = cache.clone();
+        std::thread::Builder::new()
+            .name("mpris-listener".to_string())
+            .spawn(move || {
+                let conn = match connect() {
+                    Ok(conn) => {
+                        let _ = ready_tx.send(Ok(()));
+                        conn
+                    }
+                    Err(e) => {
+                        let _ = ready_tx.send(Err(e));
+                        return;
+                    }
+                };
+                run_listener(conn, listener_cache);
+            })
+            .map_err(|e| format!("Failed to spawn MPRIS listener: {}", e))?;
+
+        ready_rx
+            .recv()
+            .map_err(|_| "MPRIS listener exited unexpectedly".to_string())??;
+
         Ok(MprisSession {
-            process_filter: process_filter.to_string(),
-            last_art_url: Mutex::new(None),
+            process_filter: ProcessFilter::parse_lossy(process_filter),
+            cache,
+            seen_generation: Mutex::new(0),
+            control_conn: Mutex::new(None),
         })
     }
 
     fn poll_current(&self) -> Option<SessionInfo> {
-        let player = match self.find_player() {
-            Ok(p) => p,
-            Err(e) => {
-                crate::log_debug!("MPRIS2: {}", e);
+        let players = self.cache.players.lock().ok()?;
+        let player = *self.matching_players(&players).first()?;
+        Some(Self::to_session_info(player))
+    }
+
+    fn list_sessions(&self) -> Vec<SessionInfo> {
+        let Ok(players) = self.cache.players.lock() else {
+            return Vec::new();
+        };
+        self.matching_players(&players)
+            .into_iter()
+            .map(Self::to_session_info)
+            .collect()
+    }
+
+    fn wait_for_change(&self, timeout: Duration) {
+        let deadline = Instant::now() + timeout;
+        let Ok(mut seen) = self.seen_generation.lock() else {
+            std::thread::sleep(timeout);
+            return;
+        };
+        let Ok(mut players) = self.cache.players.lock() else {
+            std::thread::sleep(timeout);
+            return;
+        };
+        while players.generation == *seen {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            players = match self.cache.changed.wait_timeout(players, deadline - now) {
+                Ok((guard, _)) => guard,
+                Err(_) => return,
+            };
+        }
+        *seen = players.generation;
+    }
+
+    fn get_album_art(
+        &self,
+        app_id: &str,
+        artist: &str,
+        title: &str,
+        album: &str,
+    ) -> Option<AlbumArt> {
+        let song_id = generate_song_id(title, artist, album);
+
+        if let Some(cached) = get_cached_album_art(&song_id) {
+            return Some(cached);
+        }
+
+        let art_url = {
+            let players = self.cache.players.lock().ok()?;
+            let player = players.by_name.get(app_id)?;
+            if player.art_url.is_empty() {
                 return None;
             }
+            player.art_url.clone()
         };
 
-        let metadata = match player.get_metadata() {
-            Ok(m) => m,
-            Err(e) => {
-                crate::log_debug!("MPRIS2: Failed to get metadata: {:?}", e);
-                return None;
+        let art = if art_url.starts_with("http://") || art_url.starts_with("https://") {
+            remote_art::fetch(&art_url)?
+        } else {
+            let file_path = art_url.strip_prefix("file://")?;
+            let file_path = percent_encoding::percent_decode_str(file_path).decode_utf8_lossy();
+            let data = std::fs::read(file_path.as_ref()).ok()?;
+            let mime = sniff_image_mime(&data)
+                .map(str::to_string)
+                .unwrap_or_else(|| {
+                    mime_guess::from_path(file_path.as_ref())
+                        .first_or_octet_stream()
+                        .to_string()
+                });
+            AlbumArt::new(data, &mime)
+        };
+
+        set_cached_album_art(&song_id, art.clone());
+
+        Some(art)
+    }
+
+    fn control(&self, app_id: &str, command: &PlaybackCommand) -> Result<(), String> {
+        let (track_id, position_us) = {
+            let players = self.cache.players.lock().map_err(|e| e.to_string())?;
+            let player = players
+                .by_name
+                .get(app_id)
+                .ok_or_else(|| format!("Player not found: {}", app_id))?;
+            (player.track_id.clone(), player.position_now_us())
+        };
+
+        let mut conn_guard = self.control_conn.lock().map_err(|e| e.to_string())?;
+        if conn_guard.is_none() {
+            *conn_guard = Some(
+                Connection::new_session()
+                    .map_err(|e| format!("Failed to connect to D-Bus session bus: {}", e))?,
+            );
+        }
+        let Some(conn) = conn_guard.as_ref() else {
+            return Err("D-Bus connection unavailable".to_string());
+        };
+        let proxy = conn.with_proxy(app_id, MPRIS_PATH, DBUS_TIMEOUT);
+
+        let result = match command {
+            PlaybackCommand::Play => proxy.method_call(PLAYER_INTERFACE, "Play", ()),
+            PlaybackCommand::Pause => proxy.method_call(PLAYER_INTERFACE, "Pause", ()),
+            PlaybackCommand::PlayPause => proxy.method_call(PLAYER_INTERFACE, "PlayPause", ()),
+            PlaybackCommand::Next => proxy.method_call(PLAYER_INTERFACE, "Next", ()),
+            PlaybackCommand::Previous => proxy.method_call(PLAYER_INTERFACE, "Previous", ()),
+            PlaybackCommand::Seek { position_ms } => {
+                let target_us = (*position_ms as i64).saturating_mul(1000);
+                match dbus::Path::new(track_id) {
+                    Ok(path) if !path.is_empty() => {
+                        proxy.method_call(PLAYER_INTERFACE, "SetPosition", (path, target_us))
+                    }
+                    // 没有 trackid 时退化为相对跳转
+                    _ => proxy.method_call(PLAYER_INTERFACE, "Seek", (target_us - position_us,)),
+                }
+            }
+            PlaybackCommand::SetVolume { volume } => {
+                proxy.set(PLAYER_INTERFACE, "Volume", volume.clamp(0.0, 1.0))
             }
         };
 
-        let title = metadata.title().unwrap_or_default().to_string();
-        let artists = metadata.artists().unwrap_or_default();
-        let artist = Self::format_artist_list(&artists);
-        let album = metadata.album_name().unwrap_or_default().to_string();
-        let art_url = metadata.art_url().map(|u| u.to_string()).unwrap_or_default();
-        let length_us = metadata.length().map(|d| d.as_micros() as i64).unwrap_or(0);
+        result.map_err(|e| {
+            // 连接可能已失效，下次命令时重建
+            *conn_guard = None;
+            format!("MPRIS2 command failed: {}", e)
+        })
+    }
+}
 
-        let status = player.get_playback_status().unwrap_or(PlaybackStatus::Stopped);
-        let is_playing = status == PlaybackStatus::Playing;
+/* ---------- D-Bus 监听线程 ---------- */
 
-        let position_us = player.get_position()
-            .map(|d| d.as_micros() as i64)
-            .unwrap_or(0);
+fn connect() -> Result<Connection, String> {
+    let conn = Connection::new_session()
+        .map_err(|e| format!("Failed to connect to D-Bus session bus: {}", e))?;
+    for rule in MATCH_RULES {
+        conn.add_match_no_cb(rule)
+            .map_err(|e| format!("Failed to add D-Bus match rule: {}", e))?;
+    }
+    Ok(conn)
+}
 
-        let display_name = Self::extract_display_name(&player);
-        let app_id = player.bus_name().to_string();
+/// 断线重连的间隔，连续失败时翻倍
+const RECONNECT_MIN: Duration = Duration::from_secs(1);
+const RECONNECT_MAX: Duration = Duration::from_secs(30);
 
-        if let Ok(mut cached_url) = self.last_art_url.lock() {
-            if !art_url.is_empty() {
-                *cached_url = Some(art_url.clone());
+/// 超过该时间仍未回复的属性读取会被丢弃
+const PENDING_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 已发送、等待回复的属性读取
+enum PendingCall {
+    Identity(String),
+    /// Player 接口的全部属性
+    Properties(String),
+    /// Player 接口的单个属性
+    Property(String, String),
+}
+
+impl PendingCall {
+    fn bus_name(&self) -> &str {
+        match self {
+            PendingCall::Identity(bus_name)
+            | PendingCall::Properties(bus_name)
+            | PendingCall::Property(bus_name, _) => bus_name,
+        }
+    }
+}
+
+/// 监听线程的状态。读取播放器属性时只发送请求不等待回复，
+/// 回复与信号在同一个消息循环中处理，单个播放器无响应不会阻塞其他播放器
+struct Listener {
+    conn: Connection,
+    cache: Arc<PlayerCache>,
+    /// 请求序号 -> (请求, 发送时间)
+    pending: HashMap<u32, (PendingCall, Instant)>,
+}
+
+/// 连接断开后清空缓存并重连，重连后重新订阅信号、枚举播放器
+fn run_listener(conn: Connection, cache: Arc<PlayerCache>) {
+    let mut conn = conn;
+    loop {
+        let mut listener = Listener {
+            conn,
+            cache: cache.clone(),
+            pending: HashMap::new(),
+        };
+        listener.refresh_all();
+        let error = listener.run();
+        crate::log_error!("MPRIS2: D-Bus connection lost: {}", error);
+        cache.update(|players| {
+            players.by_name.clear();
+            players.owners.clear();
+        });
+        conn = reconnect();
+    }
+}
+
+/// 按退避间隔重连，直到成功
+fn reconnect() -> Connection {
+    let mut delay = RECONNECT_MIN;
+    loop {
+        std::thread::sleep(delay);
+        match connect() {
+            Ok(conn) => {
+                crate::log_info!("MPRIS2: Reconnected to D-Bus");
+                return conn;
+            }
+            Err(e) => crate::log_warn!("MPRIS2: {}", e),
+        }
+        delay = (delay * 2).min(RECONNECT_MAX);
+    }
+}
+
+impl Listener {
+    /// 处理消息直到连接断开
+    fn run(&mut self) -> dbus::Error {
+        loop {
+            match self
+                .conn
+                .channel()
+                .blocking_pop_message(Duration::from_secs(1))
+            {
+                Ok(Some(msg)) => self.handle_message(&msg),
+                Ok(None) => {}
+                Err(e) => return e,
             }
+            self.pending
+                .retain(|_, (_, sent_at)| sent_at.elapsed() < PENDING_TIMEOUT);
         }
+    }
 
-        crate::log_info!(
-            "MPRIS2: {} - {} by {} [{}]",
-            display_name,
-            title,
-            artist,
-            if is_playing { "playing" } else { "paused" }
+    /// 枚举已存在的播放器
+    fn refresh_all(&mut self) {
+        let proxy = self.conn.with_proxy(
+            "org.freedesktop.DBus",
+            "/org/freedesktop/DBus",
+            DBUS_TIMEOUT,
         );
+        let names: Vec<String> = match proxy.method_call("org.freedesktop.DBus", "ListNames", ()) {
+            Ok((names,)) => names,
+            Err(e) => {
+                crate::log_error!("MPRIS2: Failed to list bus names: {}", e);
+                return;
+            }
+        };
 
-        Some(SessionInfo {
-            title,
-            artist,
-            album,
-            is_playing,
-            position_secs: (position_us / 1_000_000).max(0) as u64,
-            duration_secs: (length_us / 1_000_000).max(0) as u64,
-            app_id,
-            app_name: display_name,
-        })
+        let mut owners = Vec::new();
+        for name in names.into_iter().filter(|n| n.starts_with(MPRIS_PREFIX)) {
+            let owner: Result<(String,), _> =
+                proxy.method_call("org.freedesktop.DBus", "GetNameOwner", (name.as_str(),));
+            match owner {
+                Ok((owner,)) => owners.push((name, owner)),
+                Err(e) => crate::log_debug!("MPRIS2: Failed to resolve owner of {}: {}", name, e),
+            }
+        }
+
+        crate::log_info!("MPRIS2: {} players found", owners.len());
+        for (name, owner) in owners {
+            self.add_player(&name, &owner);
+        }
     }
 
-    fn get_album_art_base64(&self, artist: &str, title: &str, album: &str) -> Option<String> {
-        let song_id = generate_song_id(title, artist, album);
+    /// 发送属性读取请求，回复在 `handle_reply` 中处理
+    fn request(&mut self, call: PendingCall) {
+        let msg = match &call {
+            PendingCall::Identity(bus_name) => {
+                Message::new_method_call(bus_name, MPRIS_PATH, PROPERTIES_INTERFACE, "Get")
+                    .map(|msg| msg.append2(ROOT_INTERFACE, "Identity"))
+            }
+            PendingCall::Properties(bus_name) => {
+                Message::new_method_call(bus_name, MPRIS_PATH, PROPERTIES_INTERFACE, "GetAll")
+                    .map(|msg| msg.append1(PLAYER_INTERFACE))
+            }
+            PendingCall::Property(bus_name, property) => {
+                Message::new_method_call(bus_name, MPRIS_PATH, PROPERTIES_INTERFACE, "Get")
+                    .map(|msg| msg.append2(PLAYER_INTERFACE, property.as_str()))
+            }
+        };
+        let serial = msg.ok().and_then(|msg| self.conn.channel().send(msg).ok());
+        match serial {
+            Some(serial) => {
+                self.pending.insert(serial, (call, Instant::now()));
+            }
+            None => crate::log_debug!("MPRIS2: Failed to query {}", call.bus_name()),
+        }
+    }
 
-        if let Some(cached) = get_cached_album_art(&song_id) {
-            return Some(cached);
+    /// 先以默认名称加入缓存，名称与属性在回复到达后补全
+    fn add_player(&mut self, bus_name: &str, owner: &str) {
+        let mut state = PlayerState::new(bus_name);
+        state.identity = bus_name.trim_start_matches(MPRIS_PREFIX).to_string();
+        crate::log_debug!("MPRIS2: Player added: {}", bus_name);
+        self.cache.update(|players| {
+            players.owners.retain(|_, name| name != bus_name);
+            players
+                .owners
+                .insert(owner.to_string(), bus_name.to_string());
+            players.by_name.insert(bus_name.to_string(), state);
+        });
+        self.request(PendingCall::Identity(bus_name.to_string()));
+        self.request(PendingCall::Properties(bus_name.to_string()));
+    }
+
+    fn remove_player(&mut self, bus_name: &str) {
+        crate::log_debug!("MPRIS2: Player removed: {}", bus_name);
+        self.pending
+            .retain(|_, (call, _)| call.bus_name() != bus_name);
+        self.cache.update(|players| {
+            players.by_name.remove(bus_name);
+            players.owners.retain(|_, name| name != bus_name);
+        });
+    }
+
+    fn apply_player_props(&mut self, bus_name: &str, props: &PropMap) {
+        let mut needs_resync = false;
+        let mut changed = None;
+        self.cache.update(|players| {
+            if let Some(player) = players.by_name.get_mut(bus_name) {
+                let before = player.summary();
+                needs_resync = player.apply_player_props(props);
+                let after = player.summary();
+                if after != before && !player.title.is_empty() {
+                    changed = Some(format!("{} - {}", player.identity, after));
+                }
+            }
+        });
+        // 仅在曲目或播放状态变化时记录日志
+        if let Some(summary) = changed {
+            crate::log_info!("MPRIS2: {}", summary);
         }
 
-        let art_url = self.last_art_url.lock().ok()?.clone()?;
+        // 曲目或状态变化后 Position 不会随信号下发，需要主动读取一次
+        if needs_resync {
+            self.request(PendingCall::Property(
+                bus_name.to_string(),
+                "Position".to_string(),
+            ));
+        }
+    }
 
-        let file_path = art_url.strip_prefix("file://")?;
+    fn set_identity(&self, bus_name: &str, identity: &str) {
+        self.cache.update(|players| {
+            if let Some(player) = players.by_name.get_mut(bus_name) {
+                player.identity = identity.to_string();
+            }
+        });
+    }
 
-        let data = std::fs::read(file_path).ok()?;
+    fn handle_message(&mut self, msg: &Message) {
+        match msg.msg_type() {
+            MessageType::Signal => self.handle_signal(msg),
+            MessageType::MethodReturn | MessageType::Error => self.handle_reply(msg),
+            _ => {}
+        }
+    }
 
-        use base64::{engine::general_purpose::STANDARD, Engine};
-        let mime = mime_guess::from_path(file_path)
-            .first_or_octet_stream()
-            .to_string();
-        let data_uri = format!("data:{};base64,{}", mime, STANDARD.encode(&data));
+    fn handle_reply(&mut self, msg: &Message) {
+        let Some((call, _)) = msg
+            .get_reply_serial()
+            .and_then(|serial| self.pending.remove(&serial))
+        else {
+            return;
+        };
+        if msg.msg_type() == MessageType::Error {
+            crate::log_debug!(
+                "MPRIS2: Failed to read {} properties: {}",
+                call.bus_name(),
+                msg.read1::<&str>().unwrap_or_default()
+            );
+            return;
+        }
+
+        match call {
+            PendingCall::Identity(bus_name) => {
+                if let Ok(identity) = msg.read1::<Variant<String>>() {
+                    self.set_identity(&bus_name, &identity.0);
+                }
+            }
+            PendingCall::Properties(bus_name) => {
+                if let Ok(props) = msg.read1::<PropMap>() {
+                    self.apply_player_props(&bus_name, &props);
+                }
+            }
+            PendingCall::Property(bus_name, property) => {
+                if let Ok(value) = msg.read1::<Variant<Box<dyn RefArg>>>() {
+                    let props = PropMap::from([(property, value)]);
+                    self.apply_player_props(&bus_name, &props);
+                }
+            }
+        }
+    }
 
-        set_cached_album_art(&song_id, data_uri.clone());
+    fn handle_signal(&mut self, msg: &Message) {
+        let member = msg.member();
+        match member.as_deref() {
+            Some("NameOwnerChanged") => {
+                let Ok((name, _old, new)) = msg.read3::<String, String, String>() else {
+                    return;
+                };
+                if !name.starts_with(MPRIS_PREFIX) {
+                    return;
+                }
+                if new.is_empty() {
+                    self.remove_player(&name);
+                } else {
+                    self.add_player(&name, &new);
+                }
+            }
+            Some("PropertiesChanged") => {
+                let Some(bus_name) = resolve_sender(&self.cache, msg) else {
+                    return;
+                };
+                let Ok((interface, changed, invalidated)) =
+                    msg.read3::<String, PropMap, Vec<String>>()
+                else {
+                    return;
+                };
 
-        Some(data_uri)
+                if interface == PLAYER_INTERFACE {
+                    self.apply_player_props(&bus_name, &changed);
+                    // 只通知失效、未附带新值的属性需要重新读取
+                    for property in invalidated {
+                        self.request(PendingCall::Property(bus_name.clone(), property));
+                    }
+                } else if interface == ROOT_INTERFACE {
+                    if let Some(identity) = changed.get("Identity").and_then(|v| v.0.as_str()) {
+                        self.set_identity(&bus_name, identity);
+                    } else if invalidated.iter().any(|property| property == "Identity") {
+                        self.request(PendingCall::Identity(bus_name));
+                    }
+                }
+            }
+            Some("Seeked") => {
+                let Some(bus_name) = resolve_sender(&self.cache, msg) else {
+                    return;
+                };
+                let Ok(position) = msg.read1::<i64>() else {
+                    return;
+                };
+                self.cache.update(|players| {
+                    if let Some(player) = players.by_name.get_mut(&bus_name) {
+                        player.set_position(position);
+                    }
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+fn resolve_sender(cache: &PlayerCache, msg: &Message) -> Option<String> {
+    let sender = msg.sender()?;
+    let players = cache.players.lock().ok()?;
+    players.owners.get(&*sender).cloned()
+}
+
+/* ---------- RefArg 辅助函数 ---------- */
+
+fn unwrap_variant(arg: &dyn RefArg) -> &dyn RefArg {
+    if arg.arg_type() == ArgType::Variant
+        && let Some(inner) = arg.as_iter().and_then(|mut iter| iter.next())
+    {
+        return inner;
     }
+    arg
+}
+
+fn as_int(arg: &dyn RefArg) -> Option<i64> {
+    arg.as_i64()
+        .or_else(|| arg.as_u64().map(|v| v as i64))
+        .or_else(|| arg.as_f64().map(|v| v as i64))
+}
+
+fn as_positive(arg: &dyn RefArg) -> Option<u32> {
+    as_int(arg)
+        .and_then(|v| u32::try_from(v).ok())
+        .filter(|v| *v > 0)
+}
+
+/// `xesam:contentCreated` 为 ISO 8601 日期，部分播放器只给出年份
+fn parse_year(date: &str) -> Option<i32> {
+    let digits: String = date
+        .trim()
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    digits.get(..4)?.parse().ok()
+}
+
+fn string_list(arg: &dyn RefArg) -> Vec<String> {
+    if let Some(s) = arg.as_str() {
+        return vec![s.to_string()];
+    }
+    arg.as_iter()
+        .map(|iter| {
+            iter.filter_map(|item| unwrap_variant(item).as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
 }
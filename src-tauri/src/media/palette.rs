@@ -0,0 +1,214 @@
+use serde::{Deserialize, Serialize};
+
+/// 取色前将图片缩小到的边长
+const SAMPLE_SIZE: u32 = 64;
+
+/// 每个颜色通道保留的位数，相近的颜色归入同一格
+const CHANNEL_BITS: u32 = 3;
+
+/// 文字与背景的最低对比度（WCAG AA 正文）
+const MIN_TEXT_CONTRAST: f64 = 4.5;
+
+/// 从封面提取的配色，颜色均为 `#rrggbb`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Palette {
+    /// 占比最大的颜色
+    pub dominant: String,
+    /// 饱和度高的颜色，没有时与 `dominant` 相同
+    pub vibrant: String,
+    /// 饱和度低的颜色，没有时与 `dominant` 相同
+    pub muted: String,
+    /// 以 `dominant` 为背景时可读的文字颜色，尽量保留封面的色调
+    pub text: String,
+    /// `text` 与 `dominant` 的对比度
+    pub text_contrast: f64,
+}
+
+type Rgb = [f64; 3];
+
+struct Swatch {
+    rgb: Rgb,
+    count: u32,
+}
+
+impl Swatch {
+    /// HSV 中的饱和度与明度
+    fn saturation_value(&self) -> (f64, f64) {
+        let max = self.rgb.iter().cloned().fold(0.0, f64::max);
+        let min = self.rgb.iter().cloned().fold(255.0, f64::min);
+        let saturation = if max > 0.0 { (max - min) / max } else { 0.0 };
+        (saturation, max / 255.0)
+    }
+}
+
+fn to_hex(rgb: Rgb) -> String {
+    let [r, g, b] = rgb.map(|c| c.round().clamp(0.0, 255.0) as u8);
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+/// WCAG 相对亮度
+fn luminance(rgb: Rgb) -> f64 {
+    let [r, g, b] = rgb.map(|c| {
+        let c = c / 255.0;
+        if c <= 0.03928 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    });
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+fn contrast(a: Rgb, b: Rgb) -> f64 {
+    let (a, b) = (luminance(a), luminance(b));
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+fn mix(a: Rgb, b: Rgb, t: f64) -> Rgb {
+    [0, 1, 2].map(|i| a[i] + (b[i] - a[i]) * t)
+}
+
+/// 将背景色逐步混向白色或黑色，取最先达到对比度要求的颜色；
+/// 任何颜色与纯白或纯黑之一的对比度都不低于 4.58，总能找到结果
+fn readable_text(background: Rgb) -> Rgb {
+    const WHITE: Rgb = [255.0; 3];
+    const BLACK: Rgb = [0.0; 3];
+    let by_contrast =
+        |a: &Rgb, b: &Rgb| contrast(*a, background).total_cmp(&contrast(*b, background));
+    (1..=10)
+        .filter_map(|step| {
+            let t = step as f64 / 10.0;
+            [mix(background, WHITE, t), mix(background, BLACK, t)]
+                .into_iter()
+                .max_by(by_contrast)
+                .filter(|color| contrast(*color, background) >= MIN_TEXT_CONTRAST)
+        })
+        .next()
+        .unwrap_or_else(|| [WHITE, BLACK].into_iter().max_by(by_contrast).unwrap())
+}
+
+/// 从图片数据提取配色，无法解码或全透明时返回 `None`
+pub fn extract(data: &[u8]) -> Option<Palette> {
+    let image = image::load_from_memory(data)
+        .ok()?
+        .thumbnail(SAMPLE_SIZE, SAMPLE_SIZE)
+        .to_rgba8();
+
+    // 按量化后的颜色分格，每格记录颜色总和以求平均色
+    let shift = 8 - CHANNEL_BITS;
+    let mut bins = vec![([0u64; 3], 0u32); 1 << (CHANNEL_BITS * 3)];
+    for pixel in image.pixels() {
+        let [r, g, b, a] = pixel.0;
+        if a < 128 {
+            continue;
+        }
+        let key = ((r as usize >> shift) << (CHANNEL_BITS * 2))
+            | ((g as usize >> shift) << CHANNEL_BITS)
+            | (b as usize >> shift);
+        let (sum, count) = &mut bins[key];
+        sum[0] += r as u64;
+        sum[1] += g as u64;
+        sum[2] += b as u64;
+        *count += 1;
+    }
+
+    let swatches: Vec<Swatch> = bins
+        .into_iter()
+        .filter(|(_, count)| *count > 0)
+        .map(|(sum, count)| Swatch {
+            rgb: sum.map(|c| c as f64 / count as f64),
+            count,
+        })
+        .collect();
+
+    let dominant = swatches.iter().max_by_key(|s| s.count)?;
+    let vibrant = swatches
+        .iter()
+        .filter(|s| {
+            let (saturation, value) = s.saturation_value();
+            saturation >= 0.35 && value >= 0.3
+        })
+        .max_by(|a, b| {
+            let score = |s: &Swatch| {
+                let (saturation, value) = s.saturation_value();
+                s.count as f64 * saturation * value
+            };
+            score(a).total_cmp(&score(b))
+        })
+        .unwrap_or(dominant);
+    let muted = swatches
+        .iter()
+        .filter(|s| {
+            let (saturation, value) = s.saturation_value();
+            saturation < 0.35 && (0.2..=0.85).contains(&value)
+        })
+        .max_by_key(|s| s.count)
+        .unwrap_or(dominant);
+
+    let text = readable_text(dominant.rgb).map(f64::round);
+    Some(Palette {
+        dominant: to_hex(dominant.rgb),
+        vibrant: to_hex(vibrant.rgb),
+        muted: to_hex(muted.rgb),
+        text: to_hex(text),
+        text_contrast: (contrast(text, dominant.rgb) * 100.0).round() / 100.0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageFormat, Rgba, RgbaImage};
+    use std::io::Cursor;
+
+    fn png(image: &RgbaImage) -> Vec<u8> {
+        let mut data = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut data), ImageFormat::Png)
+            .unwrap();
+        data
+    }
+
+    #[test]
+    fn extracts_swatches() {
+        // 上部灰蓝、中部鲜红、底部透明的绿色
+        let image = RgbaImage::from_fn(SAMPLE_SIZE, SAMPLE_SIZE, |_, y| match y {
+            0..38 => Rgba([100, 110, 120, 255]),
+            38..58 => Rgba([220, 30, 30, 255]),
+            _ => Rgba([0, 255, 0, 0]),
+        });
+        let palette = extract(&png(&image)).unwrap();
+        assert_eq!(palette.dominant, "#646e78");
+        assert_eq!(palette.muted, "#646e78");
+        assert_eq!(palette.vibrant, "#dc1e1e");
+        assert!(palette.text_contrast >= MIN_TEXT_CONTRAST);
+    }
+
+    #[test]
+    fn falls_back_to_dominant() {
+        let image = RgbaImage::from_pixel(8, 8, Rgba([250, 250, 250, 255]));
+        let palette = extract(&png(&image)).unwrap();
+        assert_eq!(palette.vibrant, palette.dominant);
+        assert_eq!(palette.muted, palette.dominant);
+    }
+
+    #[test]
+    fn readable_text_on_any_background() {
+        for background in [[255.0; 3], [0.0; 3], [119.0; 3], [220.0, 30.0, 30.0]] {
+            let text = readable_text(background);
+            assert!(contrast(text, background) >= MIN_TEXT_CONTRAST);
+        }
+        // 深色背景配浅色文字，浅色背景配深色文字
+        let dark = [20.0, 20.0, 60.0];
+        assert!(luminance(readable_text(dark)) > luminance(dark));
+        let light = [240.0, 230.0, 200.0];
+        assert!(luminance(readable_text(light)) < luminance(light));
+    }
+
+    #[test]
+    fn rejects_unusable_images() {
+        assert!(extract(b"not an image").is_none());
+        let transparent = RgbaImage::from_pixel(8, 8, Rgba([255, 0, 0, 0]));
+        assert!(extract(&png(&transparent)).is_none());
+    }
+}
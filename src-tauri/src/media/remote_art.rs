@@ -0,0 +1,72 @@
+use super::{AlbumArt, sniff_image_mime};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::io::Read;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 下载封面的大小上限
+const MAX_ART_BYTES: u64 = 10 * 1024 * 1024;
+const FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+/// 下载失败的地址在此时间内不再重试
+const RETRY_AFTER: Duration = Duration::from_secs(60);
+
+static CLIENT: Lazy<Option<reqwest::blocking::Client>> = Lazy::new(|| {
+    reqwest::blocking::Client::builder()
+        .timeout(FETCH_TIMEOUT)
+        .connect_timeout(FETCH_TIMEOUT)
+        .user_agent(format!("smtc2web/{}", env!("CARGO_PKG_VERSION")))
+        .build()
+        .map_err(|e| crate::log_warn!("Failed to create album art HTTP client: {}", e))
+        .ok()
+});
+
+static FAILED_URLS: Lazy<Mutex<HashMap<String, Instant>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// 下载 http(s) 封面，只接受能识别出图片格式的内容
+pub(crate) fn fetch(url: &str) -> Option<AlbumArt> {
+    {
+        let mut failed = FAILED_URLS.lock().unwrap();
+        failed.retain(|_, at| at.elapsed() < RETRY_AFTER);
+        if failed.contains_key(url) {
+            return None;
+        }
+    }
+
+    match download(url) {
+        Ok(art) => Some(art),
+        Err(e) => {
+            crate::log_warn!("Failed to fetch album art {}: {}", url, e);
+            FAILED_URLS
+                .lock()
+                .unwrap()
+                .insert(url.to_string(), Instant::now());
+            None
+        }
+    }
+}
+
+fn download(url: &str) -> Result<AlbumArt, String> {
+    let client = CLIENT.as_ref().ok_or("HTTP client unavailable")?;
+    let resp = client.get(url).send().map_err(|e| e.to_string())?;
+
+    if !resp.status().is_success() {
+        return Err(format!("HTTP {}", resp.status()));
+    }
+    if resp.content_length().is_some_and(|len| len > MAX_ART_BYTES) {
+        return Err("image too large".to_string());
+    }
+
+    let mut data = Vec::new();
+    resp.take(MAX_ART_BYTES + 1)
+        .read_to_end(&mut data)
+        .map_err(|e| e.to_string())?;
+    if data.len() as u64 > MAX_ART_BYTES {
+        return Err("image too large".to_string());
+    }
+
+    // 不信任服务器返回的 Content-Type，以文件头为准
+    let mime = sniff_image_mime(&data).ok_or("response is not an image")?;
+    Ok(AlbumArt::new(data, mime))
+}
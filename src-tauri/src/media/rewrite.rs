@@ -0,0 +1,260 @@
+use super::filter::ProcessFilter;
+use super::{SessionInfo, split_artists};
+use crate::config::RewriteRule;
+use regex::{Regex, RegexBuilder};
+
+/// 编译后的改写规则
+struct CompiledRule {
+    app: ProcessFilter,
+    action: Action,
+}
+
+enum Action {
+    Replace {
+        field: Field,
+        pattern: Regex,
+        replacement: String,
+    },
+    Split {
+        pattern: Regex,
+    },
+}
+
+#[derive(Clone, Copy)]
+enum Field {
+    Title,
+    Artist,
+    Album,
+}
+
+impl Field {
+    fn parse(name: &str) -> Result<Self, String> {
+        match name.trim().to_lowercase().as_str() {
+            "" | "title" => Ok(Field::Title),
+            "artist" => Ok(Field::Artist),
+            "album" => Ok(Field::Album),
+            other => Err(format!("unknown field `{}`", other)),
+        }
+    }
+
+    fn get_mut(self, info: &mut SessionInfo) -> &mut String {
+        match self {
+            Field::Title => &mut info.title,
+            Field::Artist => &mut info.artist,
+            Field::Album => &mut info.album,
+        }
+    }
+}
+
+impl CompiledRule {
+    fn compile(rule: &RewriteRule) -> Result<Self, String> {
+        if rule.pattern.is_empty() {
+            return Err("empty pattern".to_string());
+        }
+        let app = ProcessFilter::parse(&rule.app).map_err(|errors| errors.join("; "))?;
+        let pattern = RegexBuilder::new(&rule.pattern)
+            .case_insensitive(true)
+            .build()
+            .map_err(|e| e.to_string())?;
+
+        let action = match rule.kind.trim().to_lowercase().as_str() {
+            "" | "replace" => Action::Replace {
+                field: Field::parse(&rule.field)?,
+                pattern,
+                replacement: rule.replacement.clone(),
+            },
+            "split" => {
+                let names: Vec<&str> = pattern.capture_names().flatten().collect();
+                if !names.contains(&"title") && !names.contains(&"artist") {
+                    return Err("split pattern needs a named group `artist` or `title`".to_string());
+                }
+                Action::Split { pattern }
+            }
+            other => return Err(format!("unknown rule type `{}`", other)),
+        };
+
+        Ok(CompiledRule { app, action })
+    }
+
+    fn apply(&self, info: &mut SessionInfo) {
+        if !self.app.matches(&info.app_id, &info.app_name) {
+            return;
+        }
+
+        match &self.action {
+            Action::Replace {
+                field,
+                pattern,
+                replacement,
+            } => {
+                let value = field.get_mut(info);
+                let replaced = pattern.replace_all(value, replacement.as_str());
+                *value = replaced.trim().to_string();
+            }
+            Action::Split { pattern } => {
+                // 只处理把艺术家写在标题里的播放器，已有艺术家时保持原样
+                if !info.artist.trim().is_empty() {
+                    return;
+                }
+                let Some(caps) = pattern.captures(&info.title) else {
+                    return;
+                };
+                let group = |name: &str| {
+                    caps.name(name)
+                        .map(|m| m.as_str().trim().to_string())
+                        .filter(|value| !value.is_empty())
+                };
+                let (artist, title) = (group("artist"), group("title"));
+                if let Some(artist) = artist {
+                    info.artist = artist;
+                }
+                if let Some(title) = title {
+                    info.title = title;
+                }
+            }
+        }
+    }
+}
+
+/// 在 `SessionInfo` 转为 `Song` 之前按顺序应用改写规则
+#[derive(Default)]
+pub struct Rewriter {
+    rules: Vec<CompiledRule>,
+}
+
+impl Rewriter {
+    /// 严格编译，返回所有出错的规则（序号从 1 开始）
+    pub fn compile(rules: &[RewriteRule]) -> Result<Self, Vec<String>> {
+        let mut compiled = Vec::new();
+        let mut errors = Vec::new();
+        for (index, rule) in rules.iter().enumerate() {
+            match CompiledRule::compile(rule) {
+                Ok(rule) => compiled.push(rule),
+                Err(e) => errors.push(format!("rule {}: {}", index + 1, e)),
+            }
+        }
+        if errors.is_empty() {
+            Ok(Rewriter { rules: compiled })
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// 宽松编译，跳过无效规则
+    pub fn compile_lossy(rules: &[RewriteRule]) -> Self {
+        let rules = rules
+            .iter()
+            .enumerate()
+            .filter_map(|(index, rule)| match CompiledRule::compile(rule) {
+                Ok(rule) => Some(rule),
+                Err(e) => {
+                    crate::log_warn!("Ignoring invalid rewrite rule {}: {}", index + 1, e);
+                    None
+                }
+            })
+            .collect();
+        Rewriter { rules }
+    }
+
+    pub fn apply(&self, info: &mut SessionInfo) {
+        let original_artist = info.artist.clone();
+        for rule in &self.rules {
+            rule.apply(info);
+        }
+        // 规则只改写连接后的字符串，艺术家列表随之重新拆分
+        if info.artist != original_artist {
+            info.artists = split_artists(&info.artist);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(kind: &str, field: &str, pattern: &str, replacement: &str) -> RewriteRule {
+        RewriteRule {
+            app: String::new(),
+            kind: kind.to_string(),
+            field: field.to_string(),
+            pattern: pattern.to_string(),
+            replacement: replacement.to_string(),
+        }
+    }
+
+    fn session(title: &str, artist: &str) -> SessionInfo {
+        SessionInfo {
+            title: title.to_string(),
+            artist: artist.to_string(),
+            artists: split_artists(artist),
+            app_id: "chrome.exe".to_string(),
+            app_name: "Google Chrome".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn compile(rules: &[RewriteRule]) -> Rewriter {
+        match Rewriter::compile(rules) {
+            Ok(rewriter) => rewriter,
+            Err(errors) => panic!("{:?}", errors),
+        }
+    }
+
+    const SPLIT: &str = r"^(?<artist>.+?) - (?<title>.+)$";
+
+    #[test]
+    fn replace_trims_result() {
+        let rewriter = compile(&[rule("replace", "title", r"\(official video\)", "")]);
+        let mut info = session("Song (Official Video)", "Band");
+        rewriter.apply(&mut info);
+        assert_eq!(info.title, "Song");
+    }
+
+    #[test]
+    fn split_fills_empty_artist() {
+        let rewriter = compile(&[rule("split", "", SPLIT, "")]);
+        let mut info = session("Band A, Band B - Song - Live", "");
+        rewriter.apply(&mut info);
+        assert_eq!(info.artist, "Band A, Band B");
+        assert_eq!(info.artists, vec!["Band A", "Band B"]);
+        assert_eq!(info.title, "Song - Live");
+    }
+
+    #[test]
+    fn split_keeps_existing_artist() {
+        let rewriter = compile(&[rule("split", "", SPLIT, "")]);
+        let mut info = session("Intro - Remastered", "Band");
+        rewriter.apply(&mut info);
+        assert_eq!(info.artist, "Band");
+        assert_eq!(info.title, "Intro - Remastered");
+    }
+
+    #[test]
+    fn rules_only_apply_to_matching_apps() {
+        let mut spotify_only = rule("replace", "album", "^$", "Unknown");
+        spotify_only.app = "spotify".to_string();
+        let mut chrome_only = spotify_only.clone();
+        chrome_only.app = "name:chrome".to_string();
+        let mut info = session("Song", "Band");
+        compile(&[spotify_only]).apply(&mut info);
+        assert_eq!(info.album, "");
+        compile(&[chrome_only]).apply(&mut info);
+        assert_eq!(info.album, "Unknown");
+    }
+
+    #[test]
+    fn invalid_rules_are_reported() {
+        let errors = Rewriter::compile(&[
+            rule("replace", "title", "ok", ""),
+            rule("replace", "title", "(", ""),
+            rule("split", "", "(?<name>.+)", ""),
+            rule("replace", "year", "x", ""),
+        ])
+        .err()
+        .unwrap();
+        assert_eq!(errors.len(), 3);
+        assert!(errors[0].starts_with("rule 2:"));
+        assert!(errors[1].starts_with("rule 3:"));
+        assert!(errors[2].starts_with("rule 4:"));
+    }
+}
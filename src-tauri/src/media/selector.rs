@@ -0,0 +1,269 @@
+use super::SessionInfo;
+use super::filter::Rule;
+use once_cell::sync::Lazy;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 手动锁定的播放器 app_id
+static PLAYER_LOCK: Lazy<Mutex<Option<String>>> = Lazy::new(|| Mutex::new(None));
+
+/// 锁定到指定播放器，传入 `None` 解除锁定
+pub fn lock_player(app_id: Option<String>) {
+    *PLAYER_LOCK.lock().unwrap() = app_id.filter(|id| !id.is_empty());
+}
+
+pub fn locked_player() -> Option<String> {
+    PLAYER_LOCK.lock().unwrap().clone()
+}
+
+/// 解析后的优先级列表，每行一条，语法与进程过滤器的单条规则相同
+#[derive(Default)]
+struct Priority {
+    text: String,
+    rules: Vec<Rule>,
+}
+
+impl Priority {
+    /// 列表内容变化时重新解析，忽略空行、注释与无效的行
+    fn update(&mut self, text: &str) {
+        if self.text == text {
+            return;
+        }
+        self.text = text.to_string();
+        self.rules = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| match Rule::parse(line) {
+                Ok(rule) => Some(rule),
+                Err(e) => {
+                    crate::log_warn!("Ignoring invalid player priority `{}`: {}", line, e);
+                    None
+                }
+            })
+            .collect();
+    }
+
+    /// 第一条匹配的规则序号，未匹配时排在最后
+    fn rank(&self, info: &SessionInfo) -> usize {
+        self.rules
+            .iter()
+            .position(|rule| rule.matches(&info.app_id, &info.app_name))
+            .unwrap_or(usize::MAX)
+    }
+}
+
+/// 从所有会话中选出 `/api/now` 展示的会话：
+/// 手动锁定优先，锁定的播放器消失时解除锁定；当前会话停止播放不足 `sticky` 时继续保留；
+/// 否则按 播放中 > 优先级列表 > 后端顺序 选择。
+pub struct SessionSelector {
+    current: Option<String>,
+    /// 当前会话最近一次处于播放状态的时间
+    last_playing: Instant,
+    priority: Priority,
+}
+
+impl SessionSelector {
+    pub fn new() -> Self {
+        Self {
+            current: None,
+            last_playing: Instant::now(),
+            priority: Priority::default(),
+        }
+    }
+
+    pub fn select<'a>(
+        &mut self,
+        sessions: &'a [SessionInfo],
+        priority: &str,
+        sticky: Duration,
+    ) -> Option<&'a SessionInfo> {
+        self.select_at(sessions, priority, sticky, Instant::now())
+    }
+
+    fn select_at<'a>(
+        &mut self,
+        sessions: &'a [SessionInfo],
+        priority: &str,
+        sticky: Duration,
+        now: Instant,
+    ) -> Option<&'a SessionInfo> {
+        self.priority.update(priority);
+
+        if let Some(locked) = locked_player() {
+            match sessions.iter().find(|s| s.app_id == locked) {
+                Some(info) => return Some(self.switch_to(info, now)),
+                None => {
+                    crate::log_info!("Locked player closed, releasing lock: {}", locked);
+                    lock_player(None);
+                }
+            }
+        }
+
+        if !sticky.is_zero()
+            && let Some(current) = self.current.as_ref()
+            && let Some(info) = sessions.iter().find(|s| &s.app_id == current)
+        {
+            if info.is_playing {
+                self.last_playing = now;
+                return Some(info);
+            }
+            if now.duration_since(self.last_playing) < sticky {
+                return Some(info);
+            }
+        }
+
+        let best = sessions
+            .iter()
+            .enumerate()
+            .min_by_key(|(index, info)| (!info.is_playing, self.priority.rank(info), *index))
+            .map(|(_, info)| info);
+
+        match best {
+            Some(info) => Some(self.switch_to(info, now)),
+            None => {
+                self.current = None;
+                None
+            }
+        }
+    }
+
+    fn switch_to<'a>(&mut self, info: &'a SessionInfo, now: Instant) -> &'a SessionInfo {
+        if self.current.as_deref() != Some(info.app_id.as_str()) {
+            crate::log_info!("Now showing player: {} ({})", info.app_name, info.app_id);
+            self.current = Some(info.app_id.clone());
+            self.last_playing = now;
+        } else if info.is_playing {
+            self.last_playing = now;
+        }
+        info
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STICKY: Duration = Duration::from_secs(10);
+
+    /// 锁定状态是全局的，测试之间需要串行
+    static SERIAL: Mutex<()> = Mutex::new(());
+
+    fn session(app_id: &str, app_name: &str, is_playing: bool) -> SessionInfo {
+        SessionInfo {
+            app_id: app_id.to_string(),
+            app_name: app_name.to_string(),
+            is_playing,
+            ..Default::default()
+        }
+    }
+
+    fn selected(
+        selector: &mut SessionSelector,
+        sessions: &[SessionInfo],
+        priority: &str,
+        now: Instant,
+    ) -> Option<String> {
+        selector
+            .select_at(sessions, priority, STICKY, now)
+            .map(|info| info.app_id.clone())
+    }
+
+    #[test]
+    fn priority_rules() {
+        let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        let start = Instant::now();
+        let sessions = [
+            session("org.mpris.MediaPlayer2.vlc", "VLC", false),
+            session("spotify.exe", "Spotify", false),
+            session("firefox.exe", "Firefox", false),
+        ];
+        let priority = "# 注释\nname:fire*\nspotify\n";
+        assert_eq!(
+            selected(&mut SessionSelector::new(), &sessions, priority, start).as_deref(),
+            Some("firefox.exe")
+        );
+        assert_eq!(
+            selected(&mut SessionSelector::new(), &sessions, "", start).as_deref(),
+            Some("org.mpris.MediaPlayer2.vlc")
+        );
+        assert_eq!(
+            selected(&mut SessionSelector::new(), &sessions, "re:[", start).as_deref(),
+            Some("org.mpris.MediaPlayer2.vlc")
+        );
+
+        // 正在播放的会话优先于优先级列表
+        let mut sessions = sessions;
+        sessions[0].is_playing = true;
+        assert_eq!(
+            selected(&mut SessionSelector::new(), &sessions, priority, start).as_deref(),
+            Some("org.mpris.MediaPlayer2.vlc")
+        );
+    }
+
+    #[test]
+    fn sticky_after_stop() {
+        let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        let start = Instant::now();
+        let mut selector = SessionSelector::new();
+        let mut sessions = [
+            session("spotify.exe", "Spotify", true),
+            session("firefox.exe", "Firefox", false),
+        ];
+        assert_eq!(
+            selected(&mut selector, &sessions, "", start).as_deref(),
+            Some("spotify.exe")
+        );
+
+        // 停止后在保持时间内仍展示原播放器
+        sessions[0].is_playing = false;
+        sessions[1].is_playing = true;
+        let later = start + Duration::from_secs(9);
+        assert_eq!(
+            selected(&mut selector, &sessions, "", later).as_deref(),
+            Some("spotify.exe")
+        );
+        let later = start + Duration::from_secs(11);
+        assert_eq!(
+            selected(&mut selector, &sessions, "", later).as_deref(),
+            Some("firefox.exe")
+        );
+
+        // 原播放器消失时立即切换
+        let mut selector = SessionSelector::new();
+        selected(&mut selector, &sessions[1..], "", start);
+        assert_eq!(
+            selected(&mut selector, &sessions[..1], "", start).as_deref(),
+            Some("spotify.exe")
+        );
+        assert_eq!(selected(&mut selector, &[], "", start), None);
+    }
+
+    #[test]
+    fn manual_lock() {
+        let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        let start = Instant::now();
+        let mut selector = SessionSelector::new();
+        let mut sessions = vec![
+            session("locked.test", "Locked", false),
+            session("spotify.exe", "Spotify", true),
+        ];
+        lock_player(Some("locked.test".to_string()));
+        // 锁定优先于播放状态、优先级与保持时间
+        assert_eq!(
+            selected(&mut selector, &sessions, "spotify", start).as_deref(),
+            Some("locked.test")
+        );
+
+        // 锁定的播放器消失后解除锁定，按正常规则选择
+        sessions.remove(0);
+        assert_eq!(
+            selected(&mut selector, &sessions, "", start).as_deref(),
+            Some("spotify.exe")
+        );
+        assert_eq!(locked_player(), None);
+
+        lock_player(Some(String::new()));
+        assert_eq!(locked_player(), None);
+    }
+}
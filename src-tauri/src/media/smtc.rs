@@ -1,9 +1,13 @@
-use super::{generate_song_id, get_cached_album_art, matches_process_filter, set_cached_album_art, MediaSession, SessionInfo};
+use super::filter::ProcessFilter;
+use super::{
+    AlbumArt, LoopStatus, MediaSession, PlaybackCommand, SessionInfo, generate_song_id,
+    get_cached_album_art, set_cached_album_art, sniff_image_mime,
+};
+use once_cell::sync::Lazy;
 use std::collections::HashMap;
 use std::sync::Mutex;
-use once_cell::sync::Lazy;
-use windows::Media::Control::GlobalSystemMediaTransportControlsSessionManager;
 use windows::Management::Deployment::PackageManager;
+use windows::Media::Control::GlobalSystemMediaTransportControlsSessionManager;
 
 static AUMID_DISPLAY_NAME_CACHE: Lazy<Mutex<HashMap<String, String>>> =
     Lazy::new(|| Mutex::new(HashMap::new()));
@@ -11,7 +15,7 @@ static AUMID_DISPLAY_NAME_CACHE: Lazy<Mutex<HashMap<String, String>>> =
 pub struct SmtcSession {
     manager: GlobalSystemMediaTransportControlsSessionManager,
     runtime: tokio::runtime::Runtime,
-    process_filter: String,
+    process_filter: ProcessFilter,
 }
 
 impl SmtcSession {
@@ -28,8 +32,7 @@ impl SmtcSession {
         }
 
         let display_name = if is_store_app(aumid) {
-            get_store_app_display_name(aumid)
-                .unwrap_or_else(|| get_fallback_display_name(aumid))
+            get_store_app_display_name(aumid).unwrap_or_else(|| get_fallback_display_name(aumid))
         } else {
             get_fallback_display_name(aumid)
         };
@@ -41,6 +44,125 @@ impl SmtcSession {
 
         display_name
     }
+
+    /// 按 AUMID 查找会话，为空时返回系统当前会话
+    fn find_session(
+        &self,
+        app_id: &str,
+    ) -> Option<windows::Media::Control::GlobalSystemMediaTransportControlsSession> {
+        if app_id.is_empty() {
+            return self.manager.GetCurrentSession().ok();
+        }
+        self.manager
+            .GetSessions()
+            .ok()?
+            .into_iter()
+            .find(|session| {
+                session
+                    .SourceAppUserModelId()
+                    .map(|id| id.to_string() == app_id)
+                    .unwrap_or(false)
+            })
+    }
+
+    /// 读取单个 SMTC 会话的信息，不匹配进程过滤器时返回 None
+    fn session_info(
+        &self,
+        session: &windows::Media::Control::GlobalSystemMediaTransportControlsSession,
+    ) -> Option<SessionInfo> {
+        let app_id = session
+            .SourceAppUserModelId()
+            .ok()
+            .and_then(|h| Some(h.to_string()))
+            .unwrap_or_default();
+
+        let app_name = self.get_app_display_name(&app_id);
+
+        if !self.process_filter.matches(&app_id, &app_name) {
+            return None;
+        }
+
+        let mut info = SessionInfo {
+            app_id,
+            app_name,
+            ..Default::default()
+        };
+
+        if let Ok(playback_info) = session.GetPlaybackInfo() {
+            use windows::Media::Control::GlobalSystemMediaTransportControlsSessionPlaybackStatus;
+            use windows::Media::MediaPlaybackAutoRepeatMode;
+            info.is_playing = playback_info.PlaybackStatus().unwrap_or_default()
+                == GlobalSystemMediaTransportControlsSessionPlaybackStatus::Playing;
+            info.shuffle = playback_info.IsShuffleActive().and_then(|v| v.Value()).ok();
+            info.loop_status = playback_info
+                .AutoRepeatMode()
+                .and_then(|v| v.Value())
+                .ok()
+                .map(|mode| match mode {
+                    MediaPlaybackAutoRepeatMode::Track => LoopStatus::Track,
+                    MediaPlaybackAutoRepeatMode::List => LoopStatus::Playlist,
+                    _ => LoopStatus::None,
+                });
+            // SMTC 不提供音量
+            info.rate = playback_info
+                .PlaybackRate()
+                .and_then(|v| v.Value())
+                .ok()
+                .filter(|rate| *rate > 0.0);
+        }
+
+        if let Ok(media_info) = session.TryGetMediaPropertiesAsync().and_then(|f| f.get()) {
+            info.title = media_info.Title().unwrap_or_default().to_string();
+            info.artist = media_info.Artist().unwrap_or_default().to_string();
+            if !info.artist.is_empty() {
+                info.artists = vec![info.artist.clone()];
+            }
+            info.album = media_info.AlbumTitle().unwrap_or_default().to_string();
+            info.album_artist = media_info.AlbumArtist().unwrap_or_default().to_string();
+            info.track_number = media_info
+                .TrackNumber()
+                .ok()
+                .and_then(|n| u32::try_from(n).ok())
+                .filter(|n| *n > 0);
+            info.track_count = media_info
+                .AlbumTrackCount()
+                .ok()
+                .and_then(|n| u32::try_from(n).ok())
+                .filter(|n| *n > 0);
+            if let Ok(genres) = media_info.Genres() {
+                info.genres = genres.into_iter().map(|g| g.to_string()).collect();
+            }
+        }
+
+        if let Ok(timeline) = session.GetTimelineProperties() {
+            let mut pos = timeline.Position().unwrap().Duration;
+            let dur = timeline.EndTime().unwrap().Duration;
+            // SMTC 只在播放器上报时更新位置，按上报时间推算当前位置
+            if info.is_playing
+                && let Ok(updated) = timeline.LastUpdatedTime()
+                && updated.UniversalTime > 0
+            {
+                let elapsed = (filetime_now() - updated.UniversalTime).max(0);
+                pos += (elapsed as f64 * info.rate.unwrap_or(1.0)) as i64;
+                if dur > 0 {
+                    pos = pos.min(dur);
+                }
+            }
+            info.position_ms = (pos.max(0) / 10_000) as u64;
+            info.duration_ms = (dur.max(0) / 10_000) as u64;
+        }
+
+        Some(info)
+    }
+}
+
+/// 当前时间，单位与 `DateTime.UniversalTime` 相同（自 1601 年起的 100 纳秒数）
+fn filetime_now() -> i64 {
+    const UNIX_EPOCH_IN_FILETIME: i64 = 116_444_736_000_000_000;
+    let since_unix = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    UNIX_EPOCH_IN_FILETIME + (since_unix.as_nanos() / 100) as i64
 }
 
 fn is_store_app(aumid: &str) -> bool {
@@ -94,71 +216,83 @@ impl MediaSession for SmtcSession {
         Ok(SmtcSession {
             manager,
             runtime,
-            process_filter: process_filter.to_string(),
+            process_filter: ProcessFilter::parse_lossy(process_filter),
         })
     }
 
     fn poll_current(&self) -> Option<SessionInfo> {
         let session = self.manager.GetCurrentSession().ok()?;
+        self.session_info(&session)
+    }
 
-        let app_id = session
-            .SourceAppUserModelId()
-            .ok()
-            .and_then(|h| Some(h.to_string()))
-            .unwrap_or_default();
-
-        let app_name = self.get_app_display_name(&app_id);
-
-        if !matches_process_filter(&self.process_filter, &app_id, &app_name) {
-            return None;
-        }
-
-        let mut info = SessionInfo {
-            app_id,
-            app_name,
-            ..Default::default()
+    fn list_sessions(&self) -> Vec<SessionInfo> {
+        let Ok(sessions) = self.manager.GetSessions() else {
+            return Vec::new();
         };
 
-        if let Ok(playback_info) = session.GetPlaybackInfo() {
-            use windows::Media::Control::GlobalSystemMediaTransportControlsSessionPlaybackStatus;
-            info.is_playing = playback_info.PlaybackStatus().unwrap_or_default()
-                == GlobalSystemMediaTransportControlsSessionPlaybackStatus::Playing;
-        }
-
-        if let Ok(media_info) = session.TryGetMediaPropertiesAsync().and_then(|f| f.get()) {
-            info.title = media_info.Title().unwrap_or_default().to_string();
-            info.artist = media_info.Artist().unwrap_or_default().to_string();
-            info.album = media_info.AlbumTitle().unwrap_or_default().to_string();
-        }
-
-        if let Ok(timeline) = session.GetTimelineProperties() {
-            let pos = timeline.Position().unwrap().Duration;
-            let dur = timeline.EndTime().unwrap().Duration;
-            info.position_secs = (pos / 10_000_000) as u64;
-            info.duration_secs = (dur / 10_000_000) as u64;
-        }
-
-        Some(info)
+        let mut infos: Vec<SessionInfo> = sessions
+            .into_iter()
+            .filter_map(|session| self.session_info(&session))
+            .collect();
+        // 正在播放的会话排在前面
+        infos.sort_by_key(|info| !info.is_playing);
+        infos
     }
 
-    fn get_album_art_base64(&self, artist: &str, title: &str, album: &str) -> Option<String> {
+    fn get_album_art(
+        &self,
+        app_id: &str,
+        artist: &str,
+        title: &str,
+        album: &str,
+    ) -> Option<AlbumArt> {
         let song_id = generate_song_id(title, artist, album);
 
         if let Some(cached) = get_cached_album_art(&song_id) {
             return Some(cached);
         }
 
-        let session = self.manager.GetCurrentSession().ok()?;
+        let session = self.find_session(app_id)?;
 
         let thumbnail_data = self.runtime.block_on(fetch_thumbnail(&session))?;
 
-        use base64::{engine::general_purpose::STANDARD, Engine};
-        let mime = "data:image/jpeg";
-        let data_uri = format!("{};base64,{}", mime, STANDARD.encode(&thumbnail_data));
+        // 缩略图不一定是 JPEG，按文件头识别
+        let mime = sniff_image_mime(&thumbnail_data).unwrap_or("image/jpeg");
+        let art = AlbumArt::new(thumbnail_data, mime);
+
+        set_cached_album_art(&song_id, art.clone());
 
-        set_cached_album_art(&song_id, data_uri.clone());
+        Some(art)
+    }
 
-        Some(data_uri)
+    fn control(&self, app_id: &str, command: &PlaybackCommand) -> Result<(), String> {
+        let session = self
+            .find_session(app_id)
+            .ok_or_else(|| format!("Session not found: {}", app_id))?;
+
+        let operation = match command {
+            PlaybackCommand::Play => session.TryPlayAsync(),
+            PlaybackCommand::Pause => session.TryPauseAsync(),
+            PlaybackCommand::PlayPause => session.TryTogglePlayPauseAsync(),
+            PlaybackCommand::Next => session.TrySkipNextAsync(),
+            PlaybackCommand::Previous => session.TrySkipPreviousAsync(),
+            PlaybackCommand::Seek { position_ms } => {
+                // SMTC 使用 100ns 为单位
+                session.TryChangePlaybackPositionAsync((*position_ms as i64).saturating_mul(10_000))
+            }
+            PlaybackCommand::SetVolume { .. } => {
+                return Err("Volume control is not supported by SMTC".to_string());
+            }
+        };
+
+        let accepted = operation
+            .and_then(|f| f.get())
+            .map_err(|e| format!("SMTC command failed: {:?}", e))?;
+        if accepted {
+            Ok(())
+        } else {
+            Err("The player rejected the command".to_string())
+        }
     }
 }
 
@@ -0,0 +1,238 @@
+use super::{AlbumArt, SessionInfo, local_file_path, sniff_image_mime};
+use crate::log_debug;
+use once_cell::sync::Lazy;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::{MetadataOptions, MetadataRevision, StandardTagKey, StandardVisualKey};
+use symphonia::core::probe::Hint;
+
+/// 最多缓存的文件标签数
+const TAG_CACHE_SIZE: usize = 16;
+
+/// 作为封面的文件夹图片，按顺序查找，不区分大小写
+const FOLDER_IMAGES: &[&str] = &["cover", "folder", "front", "album", "albumart"];
+const FOLDER_IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp"];
+
+/// 从本地音频文件读取的标签（ID3、Vorbis 注释、MP4）
+#[derive(Debug, Default)]
+pub struct FileTags {
+    pub title: String,
+    pub artists: Vec<String>,
+    pub album: String,
+    pub album_artist: String,
+    pub track_number: Option<u32>,
+    pub track_count: Option<u32>,
+    pub disc_number: Option<u32>,
+    pub genres: Vec<String>,
+    pub year: Option<i32>,
+    /// 内嵌封面，优先使用封面（front cover）类型的图片
+    pub cover: Option<AlbumArt>,
+    /// 内嵌歌词（ID3 USLT、Vorbis 注释 LYRICS / UNSYNCEDLYRICS、MP4 ©lyr），可能是 LRC 格式
+    pub lyrics: String,
+}
+
+/// 解析 `3` 或 `3/12` 形式的序号
+fn parse_number(value: &str) -> (Option<u32>, Option<u32>) {
+    let mut parts = value.split('/');
+    let mut next = || parts.next()?.trim().parse::<u32>().ok().filter(|n| *n > 0);
+    (next(), next())
+}
+
+fn set_if_empty(target: &mut String, value: &str) {
+    if target.is_empty() {
+        *target = value.to_string();
+    }
+}
+
+fn push_unique(target: &mut Vec<String>, value: &str) {
+    if !target.iter().any(|v| v == value) {
+        target.push(value.to_string());
+    }
+}
+
+impl FileTags {
+    /// 合并一组标签，已有的值优先
+    fn apply(&mut self, revision: &MetadataRevision) {
+        for tag in revision.tags() {
+            let Some(key) = tag.std_key else {
+                continue;
+            };
+            let value = tag.value.to_string();
+            let value = value.trim();
+            if value.is_empty() {
+                continue;
+            }
+            match key {
+                StandardTagKey::TrackTitle => set_if_empty(&mut self.title, value),
+                StandardTagKey::Artist => push_unique(&mut self.artists, value),
+                StandardTagKey::Album => set_if_empty(&mut self.album, value),
+                StandardTagKey::AlbumArtist => set_if_empty(&mut self.album_artist, value),
+                StandardTagKey::Lyrics => set_if_empty(&mut self.lyrics, value),
+                StandardTagKey::Genre => push_unique(&mut self.genres, value),
+                StandardTagKey::TrackNumber => {
+                    let (number, count) = parse_number(value);
+                    self.track_number = self.track_number.or(number);
+                    self.track_count = self.track_count.or(count);
+                }
+                StandardTagKey::TrackTotal => {
+                    self.track_count = self.track_count.or(parse_number(value).0);
+                }
+                StandardTagKey::DiscNumber => {
+                    self.disc_number = self.disc_number.or(parse_number(value).0);
+                }
+                StandardTagKey::Date
+                | StandardTagKey::ReleaseDate
+                | StandardTagKey::OriginalDate => {
+                    // 日期可能是 `2024`、`2024-05-01` 等格式，只取年份
+                    let year = value.get(..4).and_then(|y| y.parse::<i32>().ok());
+                    self.year = self.year.or(year.filter(|y| *y > 0));
+                }
+                _ => {}
+            }
+        }
+
+        if self.cover.is_none() {
+            let visuals = revision.visuals();
+            let visual = visuals
+                .iter()
+                .find(|v| v.usage == Some(StandardVisualKey::FrontCover))
+                .or_else(|| visuals.first());
+            self.cover = visual.map(|visual| {
+                let mime = sniff_image_mime(&visual.data).unwrap_or(&visual.media_type);
+                AlbumArt::new(visual.data.to_vec(), mime)
+            });
+        }
+    }
+}
+
+/// 读取音频文件的标签，不支持的格式返回 `None`
+pub fn read_file_tags(path: &Path) -> Option<FileTags> {
+    let file = std::fs::File::open(path).ok()?;
+    let source = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(extension);
+    }
+    let mut probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            source,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .ok()?;
+
+    // 容器前的 ID3 标签与容器内的标签都读取
+    let mut tags = FileTags::default();
+    if let Some(metadata) = probed.metadata.get()
+        && let Some(revision) = metadata.current()
+    {
+        tags.apply(revision);
+    }
+    if let Some(revision) = probed.format.metadata().current() {
+        tags.apply(revision);
+    }
+    log_debug!("Read tags from {}", path.display());
+    Some(tags)
+}
+
+/// 缓存键：路径、修改时间与大小，文件被改写（例如编辑了标签）后重新读取
+type TagCacheKey = (PathBuf, Option<SystemTime>, u64);
+
+type TagCache = Vec<(TagCacheKey, Option<Arc<FileTags>>)>;
+
+static TAG_CACHE: Lazy<Mutex<TagCache>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+/// 带缓存的 `read_file_tags`，读取失败的结果同样缓存
+fn cached_tags(path: &Path) -> Option<Arc<FileTags>> {
+    let metadata = std::fs::metadata(path).ok();
+    let key = (
+        path.to_path_buf(),
+        metadata.as_ref().and_then(|m| m.modified().ok()),
+        metadata.as_ref().map_or(0, |m| m.len()),
+    );
+    {
+        let mut cache = TAG_CACHE.lock().unwrap();
+        if let Some((_, tags)) = cache.iter().find(|(k, _)| *k == key) {
+            return tags.clone();
+        }
+        // 同一文件的旧版本不再有用
+        cache.retain(|((p, _, _), _)| p != path);
+    }
+
+    let tags = read_file_tags(path).map(Arc::new);
+    let mut cache = TAG_CACHE.lock().unwrap();
+    if cache.len() >= TAG_CACHE_SIZE {
+        cache.remove(0);
+    }
+    cache.push((key, tags.clone()));
+    tags
+}
+
+/// 本地音频文件标签中的歌词文本
+pub fn embedded_lyrics(path: &Path) -> Option<String> {
+    cached_tags(path)
+        .map(|tags| tags.lyrics.clone())
+        .filter(|lyrics| !lyrics.is_empty())
+}
+
+/// 播放器报告本地文件地址时，用文件标签补全缺失的元数据
+pub fn enrich(info: &mut SessionInfo) {
+    let Some(path) = local_file_path(&info.url) else {
+        return;
+    };
+    let Some(tags) = cached_tags(&path) else {
+        return;
+    };
+
+    set_if_empty(&mut info.title, &tags.title);
+    if info.artist.is_empty() && !tags.artists.is_empty() {
+        info.artists = tags.artists.clone();
+        info.artist = tags.artists.join(", ");
+    }
+    set_if_empty(&mut info.album, &tags.album);
+    set_if_empty(&mut info.album_artist, &tags.album_artist);
+    info.track_number = info.track_number.or(tags.track_number);
+    info.track_count = info.track_count.or(tags.track_count);
+    info.disc_number = info.disc_number.or(tags.disc_number);
+    if info.genres.is_empty() {
+        info.genres = tags.genres.clone();
+    }
+    info.year = info.year.or(tags.year);
+}
+
+/// 音频文件所在文件夹中的 `cover.jpg`、`folder.png` 等图片
+fn folder_art(audio: &Path) -> Option<AlbumArt> {
+    let images: Vec<PathBuf> = std::fs::read_dir(audio.parent()?)
+        .ok()?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| FOLDER_IMAGE_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+        })
+        .collect();
+
+    let path = FOLDER_IMAGES.iter().find_map(|name| {
+        images.iter().find(|path| {
+            path.file_stem()
+                .and_then(|s| s.to_str())
+                .is_some_and(|s| s.eq_ignore_ascii_case(name))
+        })
+    })?;
+    let data = std::fs::read(path).ok()?;
+    let mime = sniff_image_mime(&data)?;
+    Some(AlbumArt::new(data, mime))
+}
+
+/// 本地文件的封面：内嵌封面优先，其次是文件夹图片
+pub fn local_album_art(url: &str) -> Option<AlbumArt> {
+    let path = local_file_path(url)?;
+    cached_tags(&path)
+        .and_then(|tags| tags.cover.clone())
+        .or_else(|| folder_art(&path))
+}
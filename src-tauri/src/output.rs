@@ -0,0 +1,223 @@
+use crate::config::{Config, TextTemplate};
+use crate::template::Template;
+use crate::{Song, events, log_debug, log_info, log_warn, media, monotonic_ms};
+use dirs::data_dir;
+use image::codecs::jpeg::JpegEncoder;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const COVER_FILE: &str = "cover.jpg";
+const SNAPSHOT_FILE: &str = "now.json";
+const COVER_JPEG_QUALITY: u8 = 90;
+
+/// 默认输出的文本文件
+pub fn default_files() -> Vec<TextTemplate> {
+    [
+        ("title.txt", "{title}"),
+        ("artist.txt", "{artist}"),
+        ("combined.txt", crate::template::DEFAULT_TEMPLATE),
+    ]
+    .into_iter()
+    .map(|(name, template)| TextTemplate {
+        name: name.to_string(),
+        template: template.to_string(),
+    })
+    .collect()
+}
+
+/// 未配置输出目录时使用 `<数据目录>/smtc2web/output`
+pub fn resolve_dir(dir: &str) -> PathBuf {
+    if dir.trim().is_empty() {
+        let mut path = data_dir().unwrap_or_else(|| PathBuf::from("."));
+        path.push("smtc2web");
+        path.push("output");
+        path
+    } else {
+        PathBuf::from(dir.trim())
+    }
+}
+
+/// 校验输出文件：文件名只能是目录下的普通文件，且不与封面、快照重名
+pub fn validate(files: &[TextTemplate]) -> Result<(), Vec<String>> {
+    let mut errors = crate::template::validate(files).err().unwrap_or_default();
+    for (index, file) in files.iter().enumerate() {
+        let name = file.name.trim();
+        if name.is_empty() {
+            continue;
+        }
+        let plain = Path::new(name).file_name().is_some_and(|n| n == name)
+            && !name.contains(['/', '\\', ':']);
+        if !plain {
+            errors.push(format!("file {}: invalid file name `{}`", index + 1, name));
+        } else if [COVER_FILE, SNAPSHOT_FILE]
+            .iter()
+            .any(|reserved| reserved.eq_ignore_ascii_case(name))
+            || name.ends_with(".tmp")
+        {
+            errors.push(format!("file {}: `{}` is reserved", index + 1, name));
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// 先写临时文件再重命名，读取方不会读到写了一半的内容
+fn write_atomic(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    fs::write(&tmp, data).and_then(|_| fs::rename(&tmp, path))
+}
+
+/// 封面统一保存为 JPEG，其它格式解码后重新编码，无法解码时返回 `None`
+fn cover_jpeg(art: &media::AlbumArt) -> Option<Cow<'_, [u8]>> {
+    if art.mime == "image/jpeg" {
+        return Some(Cow::Borrowed(&art.data));
+    }
+    let mut data = Vec::new();
+    let result = image::load_from_memory(&art.data).and_then(|image| {
+        image
+            .to_rgb8()
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut data, COVER_JPEG_QUALITY))
+    });
+    match result {
+        Ok(()) => Some(Cow::Owned(data)),
+        Err(e) => {
+            log_debug!("Failed to convert album art {} to JPEG: {}", art.hash, e);
+            None
+        }
+    }
+}
+
+/// 将正在播放的信息写入文件，供 OBS “从文件读取”等工具使用。
+///
+/// 只在曲目、播放状态、时长或封面变化时写入，播放进度的变化不会触发，
+/// 因此文件中的 position、remaining 等字段是最近一次写入时的值。
+/// 每个文件只在内容变化时重写，封面按哈希判断是否变化。
+#[derive(Default)]
+pub struct FileOutput {
+    enabled: bool,
+    dir: PathBuf,
+    files: Vec<TextTemplate>,
+    templates: Vec<(String, Template)>,
+    /// 已写入的内容，写入失败时不记录以便下次重试
+    written: HashMap<String, String>,
+    /// 已写入封面的哈希，`Some(None)` 表示已删除
+    cover: Option<Option<String>>,
+    /// 上次成功写入时的歌曲信息
+    last: Option<Song>,
+    last_error: Option<String>,
+}
+
+impl FileOutput {
+    /// 按配置更新输出设置，设置变化后重新写入所有文件
+    pub fn configure(&mut self, config: &Config) {
+        let dir = resolve_dir(&config.file_output_dir);
+        if self.enabled == config.file_output_enabled
+            && self.dir == dir
+            && self.files == config.file_output_files
+        {
+            return;
+        }
+
+        self.enabled = config.file_output_enabled;
+        self.dir = dir;
+        self.files = config.file_output_files.clone();
+        self.templates = self
+            .files
+            .iter()
+            .filter_map(|file| match Template::parse(&file.template) {
+                Ok(template) => Some((file.name.trim().to_string(), template)),
+                Err(e) => {
+                    log_warn!("Skipping output file {}: {}", file.name, e);
+                    None
+                }
+            })
+            .collect();
+        self.written.clear();
+        self.cover = None;
+        self.last = None;
+        self.last_error = None;
+
+        if self.enabled {
+            log_info!("File output enabled: {}", self.dir.display());
+        }
+    }
+
+    /// 曲目、播放状态、时长或封面与上次写入时不同
+    fn needs_write(&self, song: &Song) -> bool {
+        self.last.as_ref().is_none_or(|last| {
+            events::track_changed(last, song)
+                || events::state_changed(last, song)
+                || last.duration_ms != song.duration_ms
+                || last.album_art != song.album_art
+        })
+    }
+
+    /// 写入文本文件、封面与 JSON 快照，失败时下次调用重试
+    pub fn write(&mut self, song: &Song) {
+        if !self.enabled || !self.needs_write(song) {
+            return;
+        }
+        let result = self.write_files(song);
+        match result {
+            Ok(()) => {
+                self.last = Some(song.clone());
+                self.last_error = None;
+            }
+            Err(e) => {
+                let error = e.to_string();
+                // 同样的错误只记录一次，避免每次轮询都刷日志
+                if self.last_error.as_ref() != Some(&error) {
+                    log_warn!(
+                        "Failed to write output files to {}: {}",
+                        self.dir.display(),
+                        error
+                    );
+                    self.last_error = Some(error);
+                }
+            }
+        }
+    }
+
+    fn write_files(&mut self, song: &Song) -> std::io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+
+        for (name, template) in &self.templates {
+            let text = template.render(song);
+            if self.written.get(name) == Some(&text) {
+                continue;
+            }
+            write_atomic(&self.dir.join(name), text.as_bytes())?;
+            self.written.insert(name.clone(), text);
+        }
+
+        let art = song
+            .album_art
+            .as_deref()
+            .and_then(|url| url.rsplit('/').next())
+            .and_then(media::get_album_art_by_hash);
+        let hash = art.as_ref().map(|art| art.hash.clone());
+        if self.cover.as_ref() != Some(&hash) {
+            let path = self.dir.join(COVER_FILE);
+            match art.as_ref().and_then(cover_jpeg) {
+                Some(data) => write_atomic(&path, &data)?,
+                None => match fs::remove_file(&path) {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+                    _ => {}
+                },
+            }
+            self.cover = Some(hash);
+        }
+
+        let mut song = song.clone();
+        song.server_time = monotonic_ms();
+        let json = serde_json::to_vec_pretty(&song).map_err(std::io::Error::other)?;
+        write_atomic(&self.dir.join(SNAPSHOT_FILE), &json)
+    }
+}
@@ -0,0 +1,357 @@
+use crate::{Song, format_duration, media::LoopStatus};
+use std::collections::HashMap;
+use warp::Filter;
+use warp::http::{Response, StatusCode, header};
+
+/// 未指定模板时使用
+pub const DEFAULT_TEMPLATE: &str = "{?artist}{artist} - {/artist}{title}";
+
+/// 纯文本模板，用于 `/api/now.txt` 等文本输出：
+///
+/// ```text
+/// {artist} — {title}              # 字段
+/// {?album}（{album}）{/album}       # 字段非空时输出
+/// {^playing}[已暂停]{/playing}     # 字段为空时输出，`{/}` 可关闭最近的段落
+/// {{ 与 }}                         # 字面量花括号
+/// ```
+///
+/// 字段：title、artist、album、album_artist、track_number、track_count、disc_number、
+/// genre、year、url、position、duration、remaining、pct，
+/// 以及仅用于条件的 playing、paused、shuffle、repeat。
+#[derive(Debug, Clone)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Title,
+    Artist,
+    Album,
+    AlbumArtist,
+    TrackNumber,
+    TrackCount,
+    DiscNumber,
+    Genre,
+    Year,
+    Url,
+    Position,
+    Duration,
+    Remaining,
+    Pct,
+    Playing,
+    Paused,
+    Shuffle,
+    Repeat,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Text(String),
+    Field(Field),
+    Section {
+        field: Field,
+        inverted: bool,
+        body: Vec<Node>,
+    },
+}
+
+impl Field {
+    fn parse(name: &str) -> Result<Self, String> {
+        Ok(match name.trim() {
+            "title" => Field::Title,
+            "artist" => Field::Artist,
+            "album" => Field::Album,
+            "album_artist" => Field::AlbumArtist,
+            "track_number" => Field::TrackNumber,
+            "track_count" => Field::TrackCount,
+            "disc_number" => Field::DiscNumber,
+            "genre" => Field::Genre,
+            "year" => Field::Year,
+            "url" => Field::Url,
+            "position" => Field::Position,
+            "duration" => Field::Duration,
+            "remaining" => Field::Remaining,
+            "pct" => Field::Pct,
+            "playing" => Field::Playing,
+            "paused" => Field::Paused,
+            "shuffle" => Field::Shuffle,
+            "repeat" => Field::Repeat,
+            other => return Err(format!("unknown field `{}`", other)),
+        })
+    }
+
+    /// 字段的文本值，条件字段为真时为 "true"，否则为空
+    fn value(self, song: &Song) -> String {
+        let flag = |on: bool| {
+            if on {
+                "true".to_string()
+            } else {
+                String::new()
+            }
+        };
+        let number = |n: Option<u32>| n.map(|n| n.to_string()).unwrap_or_default();
+        match self {
+            Field::Title => song.title.clone(),
+            Field::Artist => song.artist.clone(),
+            Field::Album => song.album.clone(),
+            Field::AlbumArtist => song.album_artist.clone(),
+            Field::TrackNumber => number(song.track_number),
+            Field::TrackCount => number(song.track_count),
+            Field::DiscNumber => number(song.disc_number),
+            Field::Genre => song.genres.join(", "),
+            Field::Year => song.year.map(|y| y.to_string()).unwrap_or_default(),
+            Field::Url => song.url.clone(),
+            Field::Position => song.position.clone().unwrap_or_default(),
+            Field::Duration => song.duration.clone().unwrap_or_default(),
+            Field::Remaining => song
+                .remaining_ms
+                .map(|ms| format_duration(ms / 1000))
+                .unwrap_or_default(),
+            Field::Pct => song.pct.map(|p| p.to_string()).unwrap_or_default(),
+            Field::Playing => flag(song.is_playing),
+            // 没有曲目时不算暂停
+            Field::Paused => flag(!song.is_playing && !song.title.is_empty()),
+            Field::Shuffle => flag(song.shuffle == Some(true)),
+            Field::Repeat => flag(song.loop_status.is_some_and(|s| s != LoopStatus::None)),
+        }
+    }
+}
+
+impl Template {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        // 段落栈：(字段, 是否取反, 外层已解析的节点)
+        let mut stack: Vec<(Field, bool, Vec<Node>)> = Vec::new();
+        let mut nodes = Vec::new();
+        let mut literal = String::new();
+        let mut rest = text;
+
+        while let Some(index) = rest.find(['{', '}']) {
+            literal.push_str(&rest[..index]);
+            let tail = &rest[index..];
+
+            if tail.starts_with("{{") || tail.starts_with("}}") {
+                literal.push_str(&tail[..1]);
+                rest = &tail[2..];
+                continue;
+            }
+            if tail.starts_with('}') {
+                return Err(format!(
+                    "unmatched `}}` at offset {}",
+                    text.len() - tail.len()
+                ));
+            }
+
+            let end = tail
+                .find('}')
+                .ok_or_else(|| format!("unclosed `{{` at offset {}", text.len() - tail.len()))?;
+            let tag = &tail[1..end];
+            rest = &tail[end + 1..];
+
+            if !literal.is_empty() {
+                nodes.push(Node::Text(std::mem::take(&mut literal)));
+            }
+
+            if let Some(name) = tag.strip_prefix('/') {
+                let (field, inverted, outer) = stack
+                    .pop()
+                    .ok_or_else(|| format!("unexpected `{{{}}}`", tag))?;
+                if !name.trim().is_empty() && Field::parse(name)? != field {
+                    return Err(format!("`{{{}}}` does not close the open section", tag));
+                }
+                let body = std::mem::replace(&mut nodes, outer);
+                nodes.push(Node::Section {
+                    field,
+                    inverted,
+                    body,
+                });
+            } else if let Some(name) = tag.strip_prefix('?') {
+                stack.push((Field::parse(name)?, false, std::mem::take(&mut nodes)));
+            } else if let Some(name) = tag.strip_prefix('^') {
+                stack.push((Field::parse(name)?, true, std::mem::take(&mut nodes)));
+            } else {
+                nodes.push(Node::Field(Field::parse(tag)?));
+            }
+        }
+
+        literal.push_str(rest);
+        if !literal.is_empty() {
+            nodes.push(Node::Text(literal));
+        }
+        if !stack.is_empty() {
+            return Err("unclosed section".to_string());
+        }
+        Ok(Template { nodes })
+    }
+
+    pub fn render(&self, song: &Song) -> String {
+        let mut out = String::new();
+        render_nodes(&self.nodes, song, &mut out);
+        out
+    }
+}
+
+fn render_nodes(nodes: &[Node], song: &Song, out: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Field(field) => out.push_str(&field.value(song)),
+            Node::Section {
+                field,
+                inverted,
+                body,
+            } => {
+                if field.value(song).is_empty() == *inverted {
+                    render_nodes(body, song, out);
+                }
+            }
+        }
+    }
+}
+
+/// 按名称查找配置中的模板
+pub fn find_named<'a>(templates: &'a [crate::config::TextTemplate], name: &str) -> Option<&'a str> {
+    templates
+        .iter()
+        .find(|t| t.name.trim().eq_ignore_ascii_case(name.trim()))
+        .map(|t| t.template.as_str())
+}
+
+/// 校验配置中的模板：名称非空且不重复，内容可以解析
+pub fn validate(templates: &[crate::config::TextTemplate]) -> Result<(), Vec<String>> {
+    let mut errors = Vec::new();
+    for (index, template) in templates.iter().enumerate() {
+        let name = template.name.trim();
+        if name.is_empty() {
+            errors.push(format!("template {}: empty name", index + 1));
+        } else if templates[..index]
+            .iter()
+            .any(|t| t.name.trim().eq_ignore_ascii_case(name))
+        {
+            errors.push(format!("template {}: duplicate name `{}`", index + 1, name));
+        }
+        if let Err(e) = Template::parse(&template.template) {
+            errors.push(format!("template {} ({}): {}", index + 1, name, e));
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// `GET /api/now.txt?format=<模板>` 或 `?template=<名称>`，都未指定时使用名为 `default` 的模板
+pub fn now_text_route(
+    state: crate::Shared,
+) -> impl Filter<Extract = (Response<String>,), Error = warp::Rejection> + Clone {
+    warp::path!("api" / "now.txt")
+        .and(warp::get())
+        .and(warp::query::<HashMap<String, String>>())
+        .map(move |query: HashMap<String, String>| {
+            let source = match (query.get("format"), query.get("template")) {
+                (Some(format), _) => Ok(format.clone()),
+                (None, Some(name)) => named_template(name).ok_or((
+                    StatusCode::NOT_FOUND,
+                    format!("Unknown template `{}`", name),
+                )),
+                (None, None) => {
+                    Ok(named_template("default").unwrap_or_else(|| DEFAULT_TEMPLATE.to_string()))
+                }
+            };
+            let result = source.and_then(|source| {
+                Template::parse(&source).map_err(|e| (StatusCode::BAD_REQUEST, e))
+            });
+
+            let (status, body) = match result {
+                Ok(template) => (StatusCode::OK, template.render(&state.read().unwrap())),
+                Err(error) => error,
+            };
+            Response::builder()
+                .status(status)
+                .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
+                .header(header::CACHE_CONTROL, "no-cache")
+                .body(body)
+                .unwrap()
+        })
+}
+
+fn named_template(name: &str) -> Option<String> {
+    let app_state = crate::APP_STATE.lock().ok()?;
+    let config = app_state.config.lock().ok()?;
+    find_named(&config.text_templates, name).map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn song() -> Song {
+        Song {
+            title: "Song".to_string(),
+            artist: "Band".to_string(),
+            is_playing: true,
+            remaining_ms: Some(125_000),
+            track_number: Some(3),
+            ..Default::default()
+        }
+    }
+
+    fn render(template: &str, song: &Song) -> String {
+        match Template::parse(template) {
+            Ok(template) => template.render(song),
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    #[test]
+    fn fields() {
+        let song = song();
+        assert_eq!(render(DEFAULT_TEMPLATE, &song), "Band - Song");
+        assert_eq!(render("#{track_number} {remaining}", &song), "#3 02:05");
+        assert_eq!(render("[{year}{track_count}]", &song), "[]");
+    }
+
+    #[test]
+    fn sections() {
+        let mut song = song();
+        let template = "{?album}[{album}] {/album}{title}{^playing} (paused){/}";
+        assert_eq!(render(template, &song), "Song");
+
+        song.album = "Album".to_string();
+        song.is_playing = false;
+        assert_eq!(render(template, &song), "[Album] Song (paused)");
+
+        song.artist.clear();
+        assert_eq!(render(DEFAULT_TEMPLATE, &song), "Song");
+    }
+
+    #[test]
+    fn nested_sections() {
+        let mut song = song();
+        let template = "{?artist}{artist}{?album} / {album}{/album}{/artist}";
+        assert_eq!(render(template, &song), "Band");
+        song.album = "Album".to_string();
+        assert_eq!(render(template, &song), "Band / Album");
+    }
+
+    #[test]
+    fn escaped_braces() {
+        assert_eq!(render("{{title}} = {title}", &song()), "{title} = Song");
+        assert_eq!(render("}}{{", &song()), "}{");
+    }
+
+    #[test]
+    fn parse_errors() {
+        for template in [
+            "{title",
+            "title}",
+            "{unknown}",
+            "{?artist}{artist}",
+            "{/artist}",
+            "{?artist}{artist}{/title}",
+        ] {
+            assert!(Template::parse(template).is_err(), "{}", template);
+        }
+    }
+}
@@ -1,62 +1,132 @@
+use crate::config::Config;
+use crate::{log_error, log_info, log_warn};
+use notify::{
+    Config as NotifyConfig, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+};
 use rust_embed::RustEmbed;
-use std::path::PathBuf;
-use warp::{Filter, path::Tail};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+use std::time::Duration;
+use warp::path::{FullPath, Tail};
+use warp::{Filter, Reply};
 
 #[derive(RustEmbed)]
 #[folder = "frontend"]
 pub struct DefaultTheme;
 
+/// 注入主题页面：订阅 `/api/ws` 的 `theme` 主题，切换主题或收到刷新推送后自动刷新。
+/// 订阅后首条消息是当前状态：首次连接时记下页面加载时的主题，
+/// 断线重连后与之比较，断线期间切换过主题则刷新。
+/// 同时订阅 `overlay`，隐藏时将整个页面设为不可见。
+/// `__PINNED__` 为固定主题的文件夹名（`/t/{folder}/`），只响应该主题的刷新推送
+const THEME_RELOAD_SCRIPT: &str = r#"<script>
+(function(){var p=__PINNED__,t=null;function c(){var f=true,w=new WebSocket((location.protocol==='https:'?'wss://':'ws://')+location.host+'/api/ws');function r(){w.onclose=null;location.reload()}w.onopen=function(){w.send(JSON.stringify({type:'subscribe',topics:['theme','overlay']}))};w.onmessage=function(m){var d=JSON.parse(m.data);if(d.type==='overlay'){document.documentElement.style.visibility=d.data.visible?'':'hidden';return}if(d.type!=='theme')return;if(f){f=false;if(t===null)t=d.data.theme;else if(!p&&d.data.theme!==t)r();return}var x=d.data.reload;if(p?x!=='*'&&x!==p:x&&x!=='*'&&x!==d.data.theme)return;r()};w.onclose=function(){setTimeout(c,2000)}}c()})();
+</script>"#;
+
+const BODY_CLOSE_TAG: &[u8] = b"</body>";
+
+fn inject_reload(mut content: Vec<u8>, pinned: Option<&str>) -> Vec<u8> {
+    let pinned = serde_json::to_string(&pinned).unwrap_or_default();
+    let script = THEME_RELOAD_SCRIPT.replace("__PINNED__", &pinned.replace("</", "<\\/"));
+    // 按字节查找，不改变页面原有的编码
+    let pos = content
+        .windows(BODY_CLOSE_TAG.len())
+        .rposition(|window| window.eq_ignore_ascii_case(BODY_CLOSE_TAG))
+        .unwrap_or(content.len());
+    content.splice(pos..pos, script.into_bytes());
+    content
+}
+
+/// 当前主题的文件服务。主题目录保存在共享状态中，每个请求读取一次，
+/// 切换主题无需重启 HTTP 服务器
 #[derive(Clone)]
 pub struct ThemeManager {
-    theme_path: PathBuf,
+    theme_path: Arc<RwLock<PathBuf>>,
+    /// 固定提供某个主题时的文件夹名，`None` 表示跟随当前主题
+    pinned: Option<String>,
 }
 
 impl ThemeManager {
     pub fn new(theme_path: &str) -> Self {
         Self {
-            theme_path: PathBuf::from(theme_path),
+            theme_path: Arc::new(RwLock::new(PathBuf::from(theme_path))),
+            pinned: None,
         }
     }
 
+    /// 固定提供已安装的某个主题，`default` 为内置主题；文件夹不存在时返回 `None`
+    pub fn for_folder(folder: &str) -> Option<Self> {
+        let theme_path = if folder == "default" {
+            PathBuf::new()
+        } else {
+            // 只接受主题目录下的一级文件夹名
+            let mut components = std::path::Path::new(folder).components();
+            if !matches!(
+                (components.next(), components.next()),
+                (Some(std::path::Component::Normal(_)), None)
+            ) {
+                return None;
+            }
+            let path = crate::theme_manager::ThemeManager::get_theme_server_path(folder);
+            if !path.is_dir() {
+                return None;
+            }
+            path
+        };
+        Some(Self {
+            theme_path: Arc::new(RwLock::new(theme_path)),
+            pinned: Some(folder.to_string()),
+        })
+    }
+
+    /// 切换到新的主题目录，空路径表示默认主题
+    pub fn set_theme_path(&self, theme_path: PathBuf) {
+        *self.theme_path.write().unwrap() = theme_path;
+    }
+
     pub async fn serve_theme_file(self, tail: Tail) -> Result<impl warp::Reply, warp::Rejection> {
         let path = tail.as_str();
         let path = if path.is_empty() { "index.html" } else { path };
+        let theme_path = self.theme_path.read().unwrap().clone();
+        let mime = mime_guess::from_path(path).first_or_octet_stream();
+        let is_html = mime.essence_str() == "text/html";
 
         // 首先尝试从自定义主题路径加载文件
         // 只有当主题路径有效且不为空时才尝试读取自定义主题
-        let has_custom_theme = !self.theme_path.to_string_lossy().is_empty()
-            && self.theme_path.components().next().is_some();
+        let has_custom_theme =
+            !theme_path.to_string_lossy().is_empty() && theme_path.components().next().is_some();
 
+        let mut content = None;
         if has_custom_theme {
-            let custom_path = self.theme_path.join(path);
+            let custom_path = theme_path.join(path);
             // 通过规范化路径并确保其仍然位于主题目录下，防止目录遍历
             if let (Ok(base_dir), Ok(resolved_path)) = (
-                std::fs::canonicalize(&self.theme_path),
+                std::fs::canonicalize(&theme_path),
                 std::fs::canonicalize(&custom_path),
             ) && resolved_path.starts_with(&base_dir)
-                && let Ok(content) = std::fs::read(&resolved_path)
             {
-                let mime = mime_guess::from_path(path).first_or_octet_stream();
-                return Ok(warp::reply::with_header(
-                    content,
-                    "content-type",
-                    mime.as_ref(),
-                ));
+                content = std::fs::read(&resolved_path).ok();
             }
         }
 
         // 否则使用默认嵌入的主题文件
-        match DefaultTheme::get(path) {
-            Some(content) => {
-                let mime = mime_guess::from_path(path).first_or_octet_stream();
-                Ok(warp::reply::with_header(
-                    content.data.to_vec(),
-                    "content-type",
-                    mime.as_ref(),
-                ))
-            }
-            None => Err(warp::reject::not_found()),
-        }
+        let content = content
+            .or_else(|| DefaultTheme::get(path).map(|file| file.data.to_vec()))
+            .ok_or_else(warp::reject::not_found)?;
+        let content = if is_html {
+            inject_reload(content, self.pinned.as_deref())
+        } else {
+            content
+        };
+
+        // 页面内容随主题变化，不允许缓存
+        Ok(warp::reply::with_header(
+            warp::reply::with_header(content, "content-type", mime.as_ref()),
+            "cache-control",
+            "no-cache",
+        ))
     }
 
     pub fn with_manager(
@@ -65,3 +135,160 @@ impl ThemeManager {
         warp::any().map(move || manager.clone())
     }
 }
+
+/// 监视器推送的事件，附带所监视的主题文件夹，用于丢弃切换前的旧事件
+type ThemeEvent = (PathBuf, notify::Result<Event>);
+
+/// 递归监视主题文件夹，同时监视主题目录的一级内容，以便发现文件夹被重新上传
+fn watch_theme(path: &Path, tx: Sender<ThemeEvent>) -> notify::Result<RecommendedWatcher> {
+    let tag = path.to_path_buf();
+    let mut watcher = RecommendedWatcher::new(
+        move |res| {
+            let _ = tx.send((tag.clone(), res));
+        },
+        NotifyConfig::default(),
+    )?;
+    if let Some(parent) = path.parent() {
+        watcher.watch(parent, RecursiveMode::NonRecursive)?;
+    }
+    if path.is_dir() {
+        watcher.watch(path, RecursiveMode::Recursive)?;
+    }
+    Ok(watcher)
+}
+
+fn start_watch(folder: &str, path: &Path, tx: &Sender<ThemeEvent>) -> Option<RecommendedWatcher> {
+    match watch_theme(path, tx.clone()) {
+        Ok(watcher) => Some(watcher),
+        Err(e) => {
+            log_error!("Failed to watch theme folder {}: {}", folder, e);
+            None
+        }
+    }
+}
+
+/// 需要监视的主题文件夹，未开启热重载或使用内置主题时为 `None`
+fn hot_reload_target(config: &Mutex<Config>) -> Option<String> {
+    let config = config.lock().ok()?;
+    let theme = config.current_theme.trim();
+    (config.theme_hot_reload && !theme.is_empty() && theme != "default").then(|| theme.to_string())
+}
+
+/// 开启热重载时监视当前主题的文件夹，在主题文件变化（包括重新上传）后推送刷新。
+///
+/// 每次等待事件前检查设置：关闭热重载时停止监视，切换主题后改为监视新主题。
+/// 300ms 内的多次变化合并为一次推送。
+pub fn start_hot_reload(config: Arc<Mutex<Config>>) {
+    thread::spawn(move || {
+        let (tx, rx) = std::sync::mpsc::channel::<ThemeEvent>();
+        // 正在监视的 (文件夹名, 路径, 监视器)，丢弃监视器即停止监视
+        let mut active: Option<(String, PathBuf, Option<RecommendedWatcher>)> = None;
+        let mut changed = false;
+
+        loop {
+            let target = hot_reload_target(&config);
+            if active.as_ref().map(|(folder, ..)| folder) != target.as_ref() {
+                changed = false;
+                active = target.map(|folder| {
+                    let path = crate::theme_manager::ThemeManager::get_theme_server_path(&folder);
+                    let watcher = start_watch(&folder, &path, &tx);
+                    if watcher.is_some() {
+                        log_info!("Watching theme for hot reload: {}", folder);
+                    }
+                    (folder, path, watcher)
+                });
+            }
+
+            match rx.recv_timeout(Duration::from_millis(300)) {
+                Ok((tag, Ok(event))) => {
+                    let Some((folder, path, watcher)) = &mut active else {
+                        continue;
+                    };
+                    if tag != *path
+                        || !matches!(
+                            event.kind,
+                            EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+                        )
+                        || !event.paths.iter().any(|p| p.starts_with(&*path))
+                    {
+                        continue;
+                    }
+                    // 文件夹被删除后重新创建，原来的递归监视已失效
+                    if event.paths.iter().any(|p| p == path) && path.is_dir() {
+                        *watcher = start_watch(folder, path, &tx);
+                    }
+                    changed = true;
+                }
+                Ok((_, Err(e))) => log_warn!("Theme watch error: {}", e),
+                Err(RecvTimeoutError::Timeout) => {
+                    if let Some((folder, ..)) = &active
+                        && std::mem::take(&mut changed)
+                    {
+                        log_info!("Theme files changed, reloading overlays: {}", folder);
+                        crate::ws::publish_theme_reload(Some(folder));
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+    });
+}
+
+/// `/t/{folder}/...`：按文件夹名同时提供多个已安装的主题，`/t/default/` 为内置主题
+pub fn pinned_theme_route()
+-> impl Filter<Extract = (warp::reply::Response,), Error = warp::Rejection> + Clone {
+    warp::path("t")
+        .and(warp::path::param::<String>())
+        .and(warp::path::full())
+        .and(warp::path::tail())
+        .and_then(|folder: String, full: FullPath, tail: Tail| async move {
+            let folder = percent_encoding::percent_decode_str(&folder)
+                .decode_utf8()
+                .map_err(|_| warp::reject::not_found())?;
+            let manager = ThemeManager::for_folder(&folder).ok_or_else(warp::reject::not_found)?;
+
+            // 缺少结尾的 `/` 时页面中的相对路径会指向 `/t/`，重定向补上
+            if tail.as_str().is_empty() && !full.as_str().ends_with('/') {
+                return Ok(warp::http::Response::builder()
+                    .status(warp::http::StatusCode::MOVED_PERMANENTLY)
+                    .header("location", format!("{}/", full.as_str()))
+                    .body(warp::hyper::Body::empty())
+                    .unwrap());
+            }
+            manager
+                .serve_theme_file(tail)
+                .await
+                .map(Reply::into_response)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn script_offset(html: &[u8]) -> usize {
+        html.windows(8).position(|w| w == b"<script>").unwrap()
+    }
+
+    #[test]
+    fn injects_before_last_body_close() {
+        let page = "<html><body>你好 İ <p>&lt;/body&gt;</p></BODY></html>".as_bytes();
+        let html = inject_reload(page.to_vec(), None);
+        let offset = script_offset(&html);
+        // 非 ASCII 内容原样保留，脚本插在 `</BODY>` 之前
+        assert_eq!(
+            &html[..offset],
+            &page[..page.len() - "</BODY></html>".len()]
+        );
+        assert!(html.ends_with(b"</script></BODY></html>"));
+    }
+
+    #[test]
+    fn appends_without_body_close() {
+        let html = inject_reload(b"<p>hi</p>".to_vec(), Some("a</script>"));
+        assert_eq!(script_offset(&html), b"<p>hi</p>".len());
+        let html = String::from_utf8(html).unwrap();
+        assert!(html.contains(r#"var p="a<\/script>""#));
+        assert!(html.ends_with("</script>"));
+    }
+}
@@ -422,7 +422,11 @@ screenshot = "screenshot.png"
                     .strip_prefix(&root_folder)
                     .unwrap_or(&normalized_file_path)
                     .strip_prefix('/')
-                    .unwrap_or(normalized_file_path.strip_prefix(&root_folder).unwrap_or(&normalized_file_path))
+                    .unwrap_or(
+                        normalized_file_path
+                            .strip_prefix(&root_folder)
+                            .unwrap_or(&normalized_file_path),
+                    )
             } else {
                 continue; // 跳过不在根文件夹内的文件
             };
@@ -2,7 +2,7 @@ use crate::i18n::{get_current_locale_data, set_locale};
 use crate::{log_info, log_warn};
 use std::process;
 use std::sync::Mutex;
-use tauri::menu::{Menu, MenuItem};
+use tauri::menu::{CheckMenuItem, Menu, MenuItem};
 use tauri::tray::{TrayIconBuilder, TrayIconEvent};
 use tauri::{AppHandle, Emitter, Manager, Runtime};
 
@@ -19,6 +19,8 @@ pub fn create_tray_menu<R: Runtime>(app: &AppHandle<R>) -> Menu<R> {
             show_window: "显示窗口".to_string(),
             open_web: "打开网页".to_string(),
             check_update: "检查更新".to_string(),
+            lock_player: "锁定当前播放器".to_string(),
+            reload_overlays: "刷新所有主题页面".to_string(),
             quit: "退出应用".to_string(),
         });
 
@@ -40,9 +42,37 @@ pub fn create_tray_menu<R: Runtime>(app: &AppHandle<R>) -> Menu<R> {
         None::<&str>,
     )
     .unwrap();
+    let lock_player = CheckMenuItem::with_id(
+        app,
+        "lock_player",
+        translations.lock_player,
+        true,
+        crate::media::selector::locked_player().is_some(),
+        None::<&str>,
+    )
+    .unwrap();
+    let reload_overlays = MenuItem::with_id(
+        app,
+        "reload_overlays",
+        translations.reload_overlays,
+        true,
+        None::<&str>,
+    )
+    .unwrap();
     let quit = MenuItem::with_id(app, "quit", translations.quit, true, None::<&str>).unwrap();
 
-    Menu::with_items(app, &[&show_window, &open_web, &check_update, &quit]).unwrap()
+    Menu::with_items(
+        app,
+        &[
+            &show_window,
+            &open_web,
+            &check_update,
+            &lock_player,
+            &reload_overlays,
+            &quit,
+        ],
+    )
+    .unwrap()
 }
 
 /// 显示窗口
@@ -72,6 +102,16 @@ pub fn handle_tray_menu_event<R: Runtime>(
         "check_update" => {
             let _ = app.emit("check-update", ());
         }
+        "lock_player" => {
+            let locked = crate::media::selector::locked_player().is_none();
+            let app_id = crate::set_player_lock(locked);
+            refresh_tray_menu(app);
+            let _ = app.emit("player-lock-changed", app_id);
+        }
+        "reload_overlays" => {
+            log_info!("Reloading all overlays");
+            crate::ws::publish_theme_reload(None);
+        }
         "quit" => {
             process::exit(0);
         }
@@ -115,6 +155,15 @@ pub fn create_tray_icon<R: Runtime>(app: &AppHandle<R>, port: u16) -> Result<(),
     Ok(())
 }
 
+/// 重建托盘菜单以同步勾选状态
+pub fn refresh_tray_menu<R: Runtime>(app: &AppHandle<R>) {
+    if let Some(tray) = app.tray_by_id("main-tray")
+        && let Err(e) = tray.set_menu(Some(create_tray_menu(app)))
+    {
+        log_warn!("Failed to update tray menu: {}", e);
+    }
+}
+
 /// 更新托盘菜单语言
 /// 实时更新托盘菜单显示语言
 pub fn update_tray_menu_language<R: Runtime>(
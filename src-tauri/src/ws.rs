@@ -0,0 +1,307 @@
+use crate::{APP_STATE, ControlRequest, Song, monotonic_ms};
+use futures_util::{SinkExt, StreamExt};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use warp::Filter;
+use warp::ws::{Message, WebSocket};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Topic {
+    Now,
+    Sessions,
+    Config,
+    Theme,
+    Overlay,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    Subscribe {
+        topics: Vec<Topic>,
+    },
+    Unsubscribe {
+        topics: Vec<Topic>,
+    },
+    Auth {
+        token: String,
+    },
+    Control {
+        #[serde(default)]
+        id: Value,
+        action: String,
+        #[serde(flatten)]
+        request: ControlRequest,
+    },
+    Overlay {
+        #[serde(default)]
+        id: Value,
+        visible: Option<bool>,
+    },
+    Ping {
+        #[serde(default)]
+        id: Value,
+    },
+}
+
+/// 推送给订阅者的消息，已序列化为 JSON 文本
+#[derive(Clone)]
+struct Broadcast {
+    topic: Topic,
+    text: Arc<String>,
+}
+
+static WS_BUS: Lazy<broadcast::Sender<Broadcast>> = Lazy::new(|| broadcast::channel(64).0);
+
+/// 主题页面是否显示，由 `overlay` 命令切换，注入主题页面的脚本据此隐藏页面
+static OVERLAY_VISIBLE: AtomicBool = AtomicBool::new(true);
+
+fn topic_message(topic: Topic, data: impl Serialize) -> String {
+    json!({ "type": topic, "data": data }).to_string()
+}
+
+fn send_to_subscribers(topic: Topic, data: impl Serialize) {
+    if WS_BUS.receiver_count() == 0 {
+        return;
+    }
+    let text = Arc::new(topic_message(topic, data));
+    let _ = WS_BUS.send(Broadcast { topic, text });
+}
+
+pub fn publish_now(song: &Song) {
+    let mut song = song.clone();
+    song.server_time = monotonic_ms();
+    send_to_subscribers(Topic::Now, song);
+}
+
+pub fn publish_sessions(entries: &[crate::SessionEntry]) {
+    send_to_subscribers(Topic::Sessions, entries);
+}
+
+pub fn publish_config(config: &crate::config::Config) {
+    send_to_subscribers(Topic::Config, theme_config(config));
+}
+
+pub fn publish_theme(theme: &str) {
+    send_to_subscribers(Topic::Theme, json!({ "theme": theme }));
+}
+
+/// 要求主题页面刷新：`Some(folder)` 只刷新该主题的页面，`None` 刷新所有页面
+pub fn publish_theme_reload(folder: Option<&str>) {
+    let Some(theme) = current_theme() else {
+        return;
+    };
+    send_to_subscribers(
+        Topic::Theme,
+        json!({ "theme": theme, "reload": folder.unwrap_or("*") }),
+    );
+}
+
+fn current_theme() -> Option<String> {
+    let config = APP_STATE.lock().ok()?.config.clone();
+    let theme = config.lock().ok()?.current_theme.clone();
+    Some(theme)
+}
+
+/// 主题页面用得到的配置项。连接无需令牌，不公开其余设置
+fn theme_config(config: &crate::config::Config) -> Value {
+    json!({
+        "current_theme": config.current_theme,
+        "locale": config.locale,
+    })
+}
+
+/// 订阅时推送的当前状态
+fn snapshot(topic: Topic) -> Option<String> {
+    let data = match topic {
+        Topic::Now => {
+            let state = APP_STATE.lock().ok()?.shared_state.clone()?;
+            let mut song = state.read().ok()?.clone();
+            song.server_time = monotonic_ms();
+            serde_json::to_value(song).ok()?
+        }
+        Topic::Sessions => {
+            let sessions = APP_STATE.lock().ok()?.shared_sessions.clone()?;
+            let entries = sessions.read().ok()?;
+            serde_json::to_value(&*entries).ok()?
+        }
+        Topic::Config => {
+            let config = APP_STATE.lock().ok()?.config.clone();
+            let config = config.lock().ok()?;
+            theme_config(&config)
+        }
+        Topic::Theme => json!({ "theme": current_theme()? }),
+        Topic::Overlay => json!({ "visible": OVERLAY_VISIBLE.load(Ordering::Relaxed) }),
+    };
+    Some(topic_message(topic, data))
+}
+
+fn result_message(id: &Value, result: Result<(), String>) -> String {
+    match result {
+        Ok(()) => json!({ "type": "result", "id": id, "ok": true }),
+        Err(e) => json!({ "type": "result", "id": id, "ok": false, "error": e }),
+    }
+    .to_string()
+}
+
+/// 单个连接的状态
+struct Connection {
+    topics: HashSet<Topic>,
+    token: Option<String>,
+}
+
+impl Connection {
+    fn authorize(&self) -> Result<(), String> {
+        crate::check_control_token(self.token.as_deref())
+    }
+
+    /// 处理一条客户端消息，返回需要回复的消息
+    async fn handle(&mut self, text: &str) -> Vec<String> {
+        let message = match serde_json::from_str::<ClientMessage>(text) {
+            Ok(message) => message,
+            Err(e) => return vec![json!({ "type": "error", "error": e.to_string() }).to_string()],
+        };
+
+        match message {
+            ClientMessage::Subscribe { topics } => {
+                self.topics.extend(topics.iter().copied());
+                let mut replies =
+                    vec![json!({ "type": "subscribed", "topics": topics }).to_string()];
+                replies.extend(topics.into_iter().filter_map(snapshot));
+                replies
+            }
+            ClientMessage::Unsubscribe { topics } => {
+                for topic in &topics {
+                    self.topics.remove(topic);
+                }
+                vec![json!({ "type": "unsubscribed", "topics": topics }).to_string()]
+            }
+            ClientMessage::Auth { token } => {
+                self.token = Some(token);
+                vec![result_message(&Value::Null, self.authorize())]
+            }
+            ClientMessage::Control {
+                id,
+                action,
+                request,
+            } => {
+                let result = match self.authorize() {
+                    Ok(()) => match crate::parse_control_action(&action, &request) {
+                        Ok(Some(command)) => {
+                            crate::run_media_command(request.app_id, command).await
+                        }
+                        Ok(None) => Err(format!("Unknown action `{}`", action)),
+                        Err(e) => Err(e),
+                    },
+                    Err(e) => Err(e),
+                };
+                vec![result_message(&id, result)]
+            }
+            ClientMessage::Overlay { id, visible } => {
+                let result = self.authorize().map(|()| {
+                    let visible = visible.unwrap_or(!OVERLAY_VISIBLE.load(Ordering::Relaxed));
+                    OVERLAY_VISIBLE.store(visible, Ordering::Relaxed);
+                    crate::log_info!("Overlay visibility: {}", visible);
+                    send_to_subscribers(Topic::Overlay, json!({ "visible": visible }));
+                });
+                vec![result_message(&id, result)]
+            }
+            ClientMessage::Ping { id } => vec![json!({ "type": "pong", "id": id }).to_string()],
+        }
+    }
+}
+
+async fn handle_socket(socket: WebSocket, token: Option<String>) {
+    let (mut sender, mut receiver) = socket.split();
+    let mut bus = WS_BUS.subscribe();
+    let mut connection = Connection {
+        topics: HashSet::new(),
+        token,
+    };
+
+    loop {
+        let outgoing = tokio::select! {
+            incoming = receiver.next() => match incoming {
+                Some(Ok(message)) if message.is_close() => break,
+                Some(Ok(message)) => match message.to_str() {
+                    Ok(text) => connection.handle(text).await,
+                    // ping/pong 由 warp 处理，忽略二进制帧
+                    Err(()) => continue,
+                },
+                Some(Err(e)) => {
+                    crate::log_debug!("WebSocket error: {}", e);
+                    break;
+                }
+                None => break,
+            },
+            update = bus.recv() => match update {
+                Ok(update) if connection.topics.contains(&update.topic) => {
+                    vec![update.text.as_ref().clone()]
+                }
+                Ok(_) => continue,
+                // 处理过慢丢失了推送，重新发送已订阅主题的当前状态
+                Err(RecvError::Lagged(_)) => {
+                    connection.topics.iter().copied().filter_map(snapshot).collect()
+                }
+                Err(RecvError::Closed) => break,
+            },
+        };
+
+        for text in outgoing {
+            if sender.send(Message::text(text)).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// `GET /api/ws`：一个连接内同时订阅状态推送并发送控制命令。
+///
+/// 所有消息均为 JSON 文本帧，以 `type` 区分。
+///
+/// 客户端 -> 服务器：
+///
+/// ```text
+/// {"type":"subscribe","topics":["now","sessions","config","theme","overlay"]}
+/// {"type":"unsubscribe","topics":["sessions"]}
+/// {"type":"auth","token":"<control token>"}
+/// {"type":"control","id":1,"action":"seek","position_ms":60000,"app_id":"..."}
+/// {"type":"overlay","id":2,"visible":false}      # 省略 visible 时切换显示状态
+/// {"type":"ping","id":3}
+/// ```
+///
+/// 服务器 -> 客户端：
+///
+/// ```text
+/// {"type":"subscribed","topics":[...]}           # 随后立即推送各主题的当前状态
+/// {"type":"unsubscribed","topics":[...]}
+/// {"type":"now","data":Song}
+/// {"type":"sessions","data":[SessionEntry]}
+/// {"type":"config","data":{"current_theme":"...","locale":"..."}}
+/// {"type":"theme","data":{"theme":"..."}}
+/// {"type":"theme","data":{"theme":"...","reload":"<folder>"}}  # 主题文件变化，"*" 表示全部
+/// {"type":"overlay","data":{"visible":true}}    # 主题页面隐藏时整页透明
+/// {"type":"result","id":1,"ok":true}
+/// {"type":"result","id":1,"ok":false,"error":"..."}
+/// {"type":"error","error":"..."}                 # 无法解析的消息
+/// {"type":"pong","id":3}
+/// ```
+///
+/// `control` 的 `action` 与 `POST /api/control/{action}` 相同。`control` 与 `overlay`
+/// 需要控制令牌，可在连接时通过 `Authorization: Bearer` 提供，浏览器中请发送 `auth` 消息。
+pub fn ws_route() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("api" / "ws")
+        .and(warp::ws())
+        .and(warp::header::optional::<String>("authorization"))
+        .map(|ws: warp::ws::Ws, authorization: Option<String>| {
+            let token = crate::bearer_token(authorization.as_deref()).map(str::to_string);
+            ws.on_upgrade(move |socket| handle_socket(socket, token))
+        })
+}
@@ -19,6 +19,10 @@ import {
   faDownload,
   faRotate,
   faCircleUp,
+  faLock,
+  faLockOpen,
+  faPlus,
+  faEye,
 } from "@fortawesome/free-solid-svg-icons";
 
 // Add icons to the library
@@ -41,4 +45,8 @@ library.add(
   faDownload,
   faRotate,
   faCircleUp,
+  faLock,
+  faLockOpen,
+  faPlus,
+  faEye,
 );
@@ -10,14 +10,34 @@ export const useConfigStore = defineStore("config", () => {
     current_theme: "",
     locale: "zh-CN",
     process_filter: "*",
+    player_priority: "",
+    sticky_seconds: 10,
     update_source: "github",
     auto_check_update: true,
     autostart: false,
+    control_token: "",
+    art_cache_max_mb: 200,
+    art_cache_max_age_days: 30,
+    rewrite_rules: [],
+    text_templates: [
+      { name: "default", template: "{?artist}{artist} - {/artist}{title}" },
+    ],
+    file_output_enabled: false,
+    file_output_dir: "",
+    file_output_files: [
+      { name: "title.txt", template: "{title}" },
+      { name: "artist.txt", template: "{artist}" },
+      { name: "combined.txt", template: "{?artist}{artist} - {/artist}{title}" },
+    ],
+    theme_hot_reload: false,
+    lyrics_dir: "",
+    history_enabled: false,
   });
 
   const loading = ref(false);
   const saved = ref(false);
   const currentAppId = ref("");
+  const playerLock = ref<string | null>(null);
 
   async function loadConfig() {
     try {
@@ -62,13 +82,36 @@ export const useConfigStore = defineStore("config", () => {
     return "";
   }
 
+  async function getPlayerLock() {
+    try {
+      if (hasTauri()) {
+        playerLock.value = await tauriInvoke<string | null>("get_player_lock");
+      }
+    } catch (e) {
+      console.error("获取播放器锁定状态失败:", e);
+    }
+  }
+
+  async function setPlayerLock(locked: boolean) {
+    try {
+      if (hasTauri()) {
+        playerLock.value = await tauriInvoke<string | null>("lock_player", { locked });
+      }
+    } catch (e) {
+      console.error("锁定播放器失败:", e);
+    }
+  }
+
   return {
     config,
     loading,
     saved,
     currentAppId,
+    playerLock,
     loadConfig,
     saveConfig,
     getCurrentAppId,
+    getPlayerLock,
+    setPlayerLock,
   };
 });
@@ -75,6 +75,17 @@ export const useThemeStore = defineStore("theme", () => {
     }
   }
 
+  async function reloadOverlays() {
+    if (!hasTauri()) return;
+    try {
+      await tauriInvoke("reload_overlays");
+      toast.success(t("messages.theme.reloadSuccess"));
+    } catch (e: any) {
+      const message = e.message || e.toString() || String(e);
+      toast.error(t("messages.theme.reloadError", { message }));
+    }
+  }
+
   async function deleteTheme(theme: Theme) {
     toast.confirm(
       t("messages.theme.deleteConfirm", { name: theme.name }),
@@ -170,6 +181,7 @@ export const useThemeStore = defineStore("theme", () => {
     loadCurrentTheme,
     selectTheme,
     deleteTheme,
+    reloadOverlays,
     uploadTheme,
     getScreenshotUrl,
   };
@@ -1,13 +1,64 @@
+/** 元数据改写规则 */
+export interface RewriteRule {
+  /** 生效的应用，语法同进程过滤器，为空时对所有应用生效 */
+  app: string;
+  /** "replace" | "split" */
+  kind: string;
+  /** "title" | "artist" | "album"，仅 replace 使用 */
+  field: string;
+  pattern: string;
+  replacement: string;
+}
+
+/** 命名的文本模板，用于 /api/now.txt?template=<name> */
+export interface TextTemplate {
+  name: string;
+  template: string;
+}
+
+export interface RewritePreview {
+  app_id: string;
+  app_name: string;
+  before: { title: string; artist: string; album: string };
+  after: { title: string; artist: string; album: string };
+}
+
 export interface AppConfig {
   server_port: number;
   address: string;
   current_theme: string;
   locale: string;
   process_filter: string;
+  /** 播放器优先级，每行一个关键字 */
+  player_priority: string;
+  /** 当前播放器停止后继续保持的秒数，0 表示不保持 */
+  sticky_seconds: number;
   /** 更新源: "github" | "official" */
   update_source: string;
   /** 是否启用自动检查更新 */
   auto_check_update: boolean;
   /** 是否开机自启动 */
   autostart: boolean;
+  /** 播放控制接口令牌，为空时禁用 */
+  control_token: string;
+  /** 封面磁盘缓存上限（MB） */
+  art_cache_max_mb: number;
+  /** 封面磁盘缓存保留天数，0 表示不限 */
+  art_cache_max_age_days: number;
+  /** 元数据改写规则，按顺序应用 */
+  rewrite_rules: RewriteRule[];
+  /** 命名的文本模板 */
+  text_templates: TextTemplate[];
+  /** 是否将正在播放的信息写入文件 */
+  file_output_enabled: boolean;
+  /** 文件输出目录，为空时使用默认目录 */
+  file_output_dir: string;
+  /** 输出的文本文件，name 为文件名 */
+  file_output_files: TextTemplate[];
+  /** 主题文件变化时自动刷新已打开的主题页面 */
+  theme_hot_reload: boolean;
+  /** 歌词文件夹，为空时使用默认目录 */
+  lyrics_dir: string;
+  /** 是否记录播放历史 */
+  history_enabled: boolean;
 }